@@ -0,0 +1,312 @@
+use std::io::{self, IsTerminal};
+
+/// Where the commands to run come from.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    /// Typed at a prompt.
+    Interactive,
+    /// A script file, e.g. from a `#!` line.
+    Script(String),
+    /// The code given with `-c`.
+    Code(String),
+    /// A script read from standard input, with `-s` or when it's not a
+    /// terminal.
+    Stdin,
+}
+
+/// How the shell was asked to run.
+#[derive(Debug, PartialEq)]
+pub struct Arguments {
+    pub source: Source,
+    /// The name the code is run as, which becomes `@0`.
+    pub name: String,
+    /// The arguments for the script, which become `@1`, `@2`... and `@args`.
+    pub args: Vec<String>,
+    /// `-n`: check the syntax without running anything.
+    pub check_only: bool,
+    /// `--login`: read the profile before anything else.
+    pub login: bool,
+    /// `--norc`: don't read the startup file of an interactive shell.
+    pub norc: bool,
+    /// Shell options to turn on, e.g. `trace-exec`.
+    pub options: Vec<String>,
+}
+
+/// What `main` should do after reading its arguments.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Arguments),
+    /// `--help` or `--version`, with the text to show.
+    Show(String),
+    /// A usage error, with a message.
+    Fail(String),
+}
+
+pub const USAGE: &str = "\
+Usage: insh [options] [script [args...]]
+       insh [options] -c code [name [args...]]
+       insh [options] -s [args...]
+
+Options:
+  -c code          run code rather than a script; the name after it is @0
+  -s               read the script from standard input
+  -n               check the syntax without running anything
+  -l, --login      read ~/.insh_profile first, as a login shell does
+  --norc           don't read ~/.inshrc in an interactive shell
+  --trace-tokens   print each token as it's read
+  --dump-bytecode  print the bytecode of everything compiled
+  --trace-exec     print each op as it's run
+  --help           show this help
+  --version        show the version
+
+Arguments after the script (or the code's name) become @1, @2... and the
+list @args, with their number in @argc.";
+
+/// Reads the shell's arguments, not including the program name. A program
+/// name starting with `-` makes a login shell, as `login` runs shells.
+pub fn parse(program: &str, args: impl IntoIterator<Item = String>) -> Command {
+    parse_with(program, args, io::stdin().is_terminal())
+}
+
+/// Reads the shell's arguments as `parse` does, given whether standard input
+/// is a terminal.
+fn parse_with(
+    program: &str,
+    args: impl IntoIterator<Item = String>,
+    stdin_is_terminal: bool,
+) -> Command {
+    let mut args = args.into_iter();
+    let mut arguments = Arguments {
+        source: Source::Interactive,
+        name: "insh".to_owned(),
+        args: Vec::new(),
+        check_only: false,
+        login: program.starts_with('-'),
+        norc: false,
+        options: Vec::new(),
+    };
+    let mut from_stdin = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => return Command::Show(USAGE.to_owned()),
+            "--version" => return Command::Show(format!("insh {}", env!("CARGO_PKG_VERSION"))),
+            "--login" | "-l" => arguments.login = true,
+            "--norc" => arguments.norc = true,
+            "--trace-tokens" | "--dump-bytecode" | "--trace-exec" => {
+                arguments.options.push(arg[2..].to_owned());
+            }
+            "-n" => arguments.check_only = true,
+            "-s" => from_stdin = true,
+            "-c" => match args.next() {
+                Some(code) => {
+                    arguments.source = Source::Code(code);
+                    if let Some(name) = args.next() {
+                        arguments.name = name;
+                    }
+                    break;
+                }
+                None => return Command::Fail("-c: expected some code to run".to_owned()),
+            },
+            // `--` ends the options, so that a script can start with `-`.
+            "--" => {
+                if !from_stdin {
+                    if let Some(script) = args.next() {
+                        arguments.name = script.clone();
+                        arguments.source = Source::Script(script);
+                    }
+                }
+                break;
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                return Command::Fail(format!("unknown option `{}`", option));
+            }
+            _ if from_stdin => {
+                arguments.args.push(arg);
+                break;
+            }
+            _ => {
+                arguments.name = arg.clone();
+                arguments.source = Source::Script(arg);
+                break;
+            }
+        }
+    }
+
+    arguments.args.extend(args);
+
+    // With nothing to run, commands are read from standard input: typed at
+    // a prompt if it's a terminal, or else as a script, e.g. `insh < file`.
+    if matches!(arguments.source, Source::Interactive) && (from_stdin || !stdin_is_terminal) {
+        arguments.source = Source::Stdin;
+    }
+
+    Command::Run(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What running with `source` and no options gives, named `name`.
+    fn run(source: Source, name: &str, args: &[&str]) -> Arguments {
+        Arguments {
+            source,
+            name: name.to_owned(),
+            args: args.iter().map(|&arg| arg.to_owned()).collect(),
+            check_only: false,
+            login: false,
+            norc: false,
+            options: Vec::new(),
+        }
+    }
+
+    fn parse_args(args: &[&str], stdin_is_terminal: bool) -> Command {
+        parse_with(
+            "insh",
+            args.iter().map(|&arg| arg.to_owned()),
+            stdin_is_terminal,
+        )
+    }
+
+    #[test]
+    fn parses_sources_and_names() {
+        let code = |code: &str| Source::Code(code.to_owned());
+        let script = |path: &str| Source::Script(path.to_owned());
+        let cases = [
+            (vec![], run(Source::Interactive, "insh", &[])),
+            (vec!["-c", "echo"], run(code("echo"), "insh", &[])),
+            (vec!["-c", "echo", "me"], run(code("echo"), "me", &[])),
+            (
+                vec!["-c", "echo", "me", "a", "-b"],
+                run(code("echo"), "me", &["a", "-b"]),
+            ),
+            (vec!["-c", "-n"], run(code("-n"), "insh", &[])),
+            (
+                vec!["a.insh", "x", "-c"],
+                run(script("a.insh"), "a.insh", &["x", "-c"]),
+            ),
+            (
+                vec!["--", "-a.insh", "x"],
+                run(script("-a.insh"), "-a.insh", &["x"]),
+            ),
+            (vec!["--"], run(Source::Interactive, "insh", &[])),
+            (vec!["-s"], run(Source::Stdin, "insh", &[])),
+            (
+                vec!["-s", "a", "-b"],
+                run(Source::Stdin, "insh", &["a", "-b"]),
+            ),
+            (vec!["-s", "--", "-a"], run(Source::Stdin, "insh", &["-a"])),
+            (vec!["-", "x"], run(script("-"), "-", &["x"])),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(
+                parse_args(&args, true),
+                Command::Run(expected),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn reads_stdin_when_it_is_not_a_terminal() {
+        let cases = [
+            (vec![], Source::Stdin),
+            (vec!["-s"], Source::Stdin),
+            (vec!["-c", "echo"], Source::Code("echo".to_owned())),
+            (vec!["a.insh"], Source::Script("a.insh".to_owned())),
+        ];
+
+        for (args, source) in cases {
+            let Command::Run(arguments) = parse_args(&args, false) else {
+                panic!("{:?} didn't run", args);
+            };
+            assert_eq!(arguments.source, source, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn parses_options() {
+        let cases = [
+            (
+                vec!["-n", "a.insh"],
+                Arguments {
+                    check_only: true,
+                    ..run(Source::Script("a.insh".to_owned()), "a.insh", &[])
+                },
+            ),
+            (
+                vec!["--norc"],
+                Arguments {
+                    norc: true,
+                    ..run(Source::Interactive, "insh", &[])
+                },
+            ),
+            (
+                vec!["-l", "--login"],
+                Arguments {
+                    login: true,
+                    ..run(Source::Interactive, "insh", &[])
+                },
+            ),
+            (
+                vec!["--trace-exec", "--dump-bytecode", "-c", "echo"],
+                Arguments {
+                    options: vec!["trace-exec".to_owned(), "dump-bytecode".to_owned()],
+                    ..run(Source::Code("echo".to_owned()), "insh", &[])
+                },
+            ),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(
+                parse_args(&args, true),
+                Command::Run(expected),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn program_name_starting_with_dash_is_a_login_shell() {
+        let Command::Run(arguments) = parse_with("-insh", Vec::new(), true) else {
+            panic!("a login shell didn't run");
+        };
+        assert!(arguments.login);
+    }
+
+    #[test]
+    fn help_and_version_stop_at_once() {
+        let version = Command::Show(format!("insh {}", env!("CARGO_PKG_VERSION")));
+        let cases = [
+            (vec!["--help"], Command::Show(USAGE.to_owned())),
+            (
+                vec!["-n", "--help", "--bogus"],
+                Command::Show(USAGE.to_owned()),
+            ),
+            (vec!["--version"], version),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse_args(&args, true), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_options_and_missing_code() {
+        let fail = |message: &str| Command::Fail(message.to_owned());
+        let cases = [
+            (vec!["--bogus"], fail("unknown option `--bogus`")),
+            (vec!["-x", "a.insh"], fail("unknown option `-x`")),
+            (vec!["-s", "-z"], fail("unknown option `-z`")),
+            (vec!["-c"], fail("-c: expected some code to run")),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse_args(&args, true), expected, "{:?}", args);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use std::num::IntErrorKind;
+
 use crate::{
+    diagnostic::Location,
     scan::{
         scanner::Scanner,
         tokens::{Token, TokenType},
@@ -6,7 +9,8 @@ use crate::{
     vm::{
         chunk::bytecode_chunk::ByteCodeChunk,
         op::Op,
-        value::{fvalue, ivalue, Value},
+        redirect::RedirectKind,
+        value::{fvalue, ivalue},
     },
 };
 
@@ -15,10 +19,45 @@ use super::{
     precedence::{ParseRule, Precedence},
 };
 
+fn redirect_kind(token_type: TokenType) -> Option<RedirectKind> {
+    match token_type {
+        TokenType::RedirectOutput => Some(RedirectKind::Output),
+        TokenType::RedirectAppend => Some(RedirectKind::Append),
+        TokenType::RedirectInput => Some(RedirectKind::Input),
+        TokenType::RedirectError => Some(RedirectKind::Error),
+        TokenType::RedirectErrorToOutput => Some(RedirectKind::ErrorToOutput),
+        TokenType::RedirectAll => Some(RedirectKind::All),
+        _ => None,
+    }
+}
+
+/// Parses an int literal, telling one that's out of range apart from one
+/// that isn't a number at all.
+fn parse_int(text: &str) -> Result<ivalue, CompileError> {
+    text.parse::<ivalue>().map_err(|e| match e.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+            CompileError::IntOutOfRange(text.to_owned())
+        }
+        _ => CompileError::InvalidNumber(text.to_owned()),
+    })
+}
+
 pub struct LocalVariable {
     depth: i64,
 }
 
+/// A loop being compiled, for `break` and `continue` to find.
+pub struct Loop {
+    /// Where `continue` jumps back to, if already known. The condition of a
+    /// `do ... while` loop isn't compiled until after its body.
+    start_offset: Option<usize>,
+    /// The scope depth outside the loop body; scopes opened inside it must be
+    /// closed before jumping out.
+    scope_depth: i64,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
 pub struct Compiler {
     pub(super) chunk: ByteCodeChunk,
 
@@ -32,6 +71,8 @@ pub struct Compiler {
     pub(super) locals: Vec<LocalVariable>,
     pub(super) local_count: usize,
     pub(super) scope_depth: i64,
+
+    pub(super) loops: Vec<Loop>,
 }
 
 impl Compiler {
@@ -42,22 +83,55 @@ impl Compiler {
             previous: Token::new(TokenType::Unknown, String::new()),
             current: Token::new(TokenType::Unknown, String::new()),
 
-            debug_output_chunk: true,
+            debug_output_chunk: false,
 
             locals: Vec::new(),
             local_count: 0,
             scope_depth: 0,
+
+            loops: Vec::new(),
         }
     }
 
+    /// Turns printing of each token as it's read on or off.
+    pub fn set_trace_tokens(&mut self, on: bool) {
+        self.scanner.set_debug_output(on);
+    }
+
+    /// Turns printing of the compiled chunk on or off.
+    pub fn set_dump_bytecode(&mut self, on: bool) {
+        self.debug_output_chunk = on;
+    }
+
     pub fn into_chunk(self) -> ByteCodeChunk {
         self.chunk
     }
 
+    /// Where in the source an error returned by [`Compiler::compile_to`] was
+    /// found.
+    pub fn error_location(&self, error: &CompileError) -> Location {
+        match error {
+            CompileError::MissingToken(_, token) | CompileError::MissingExpression(token) => {
+                Location::of(token)
+            }
+            CompileError::ScanError(_) => {
+                let (line, column, length) = self.scanner.token_position();
+                Location {
+                    line,
+                    column,
+                    length,
+                }
+            }
+            _ => Location::of(&self.previous),
+        }
+    }
+
     pub(super) fn advance(&mut self) -> Result<(), CompileError> {
         match self.scanner.read_token() {
             Ok(token) => {
                 self.previous = std::mem::replace(&mut self.current, token);
+                self.chunk
+                    .set_position(self.previous.line, self.previous.column);
                 Ok(())
             }
             Err(e) => Err(CompileError::ScanError(e)),
@@ -86,25 +160,38 @@ impl Compiler {
     }
 
     pub(super) fn unary(&mut self, _: bool) -> Result<(), CompileError> {
-        let operator_type = self.previous.token_type;
+        let operator = self.previous.clone();
+        let operator_type = operator.token_type;
 
         self.parse_precedence(Precedence::Unary as u8)?;
+        self.chunk.set_position(operator.line, operator.column);
 
         match operator_type {
             TokenType::Minus => self.emit_op(Op::Negate),
             TokenType::Plus => {}
-            _ => return Err(CompileError::UnknownUnaryOperator),
+            TokenType::Bang | TokenType::Not => self.emit_op(Op::Not),
+            TokenType::Len => self.emit_op(Op::Length),
+            other => return Err(CompileError::UnknownUnaryOperator(other)),
         }
 
         Ok(())
     }
 
     pub(super) fn binary(&mut self, _: bool) -> Result<(), CompileError> {
-        let operator_type = self.previous.token_type;
+        let operator = self.previous.clone();
+        let operator_type = operator.token_type;
+
+        // A pipeline can carry on to the next line after a trailing `|`.
+        if operator_type == TokenType::Pipe {
+            self.skip_line_ends()?;
+        }
 
         let prec = ParseRule::precedence_for(operator_type) as u8;
         self.parse_precedence(prec + 1)?;
 
+        // Errors in the operation are reported at the operator.
+        self.chunk.set_position(operator.line, operator.column);
+
         match operator_type {
             TokenType::Plus => self.emit_op(Op::Add),
             TokenType::Minus => self.emit_op(Op::Subtract),
@@ -112,25 +199,174 @@ impl Compiler {
             TokenType::Slash => self.emit_op(Op::Divide),
             TokenType::Pipe => self.emit_op(Op::Pipe),
             TokenType::EqualEqual => self.emit_op(Op::Equal),
+            TokenType::BangEqual => self.emit_op(Op::NotEqual),
+            TokenType::Less => self.emit_op(Op::Less),
+            TokenType::LessEqual => self.emit_op(Op::LessEqual),
+            TokenType::Greater => self.emit_op(Op::Greater),
+            TokenType::GreaterEqual => self.emit_op(Op::GreaterEqual),
+            TokenType::LessEqualGreater => self.emit_op(Op::Compare),
+            TokenType::DotDot => self.emit_op(Op::Range),
+            TokenType::In => self.emit_op(Op::Contains),
             _ => {}
         }
 
         Ok(())
     }
 
+    pub(super) fn background(&mut self, _: bool) -> Result<(), CompileError> {
+        // <command or pipeline> &
+        self.chunk
+            .set_position(self.previous.line, self.previous.column);
+        self.emit_op(Op::Background);
+        Ok(())
+    }
+
+    pub(super) fn list(&mut self, _: bool) -> Result<(), CompileError> {
+        // [<expr>, <expr>, ...]
+        let mut count = 0;
+
+        if !self.check(TokenType::CloseSquare) {
+            loop {
+                self.expression()?;
+                count += 1;
+
+                if !self.match_type(TokenType::Comma)? {
+                    break;
+                }
+            }
+        }
+
+        self.consume(&[TokenType::CloseSquare])?;
+
+        self.emit_int_constant(count);
+        self.emit_op(Op::List);
+        Ok(())
+    }
+
+    pub(super) fn index(&mut self, _: bool) -> Result<(), CompileError> {
+        // <expr>[<index>] or <expr>[<from>:<to>], where either end of a slice
+        // may be left out.
+        let bracket = self.previous.clone();
+        let is_slice = if self.match_type(TokenType::Colon)? {
+            self.emit_none();
+            true
+        } else {
+            self.expression()?;
+            self.match_type(TokenType::Colon)?
+        };
+
+        if is_slice {
+            if self.check(TokenType::CloseSquare) {
+                self.emit_none();
+            } else {
+                self.expression()?;
+            }
+            self.chunk.set_position(bracket.line, bracket.column);
+            self.emit_op(Op::Slice);
+        } else {
+            self.chunk.set_position(bracket.line, bracket.column);
+            self.emit_op(Op::Index);
+        }
+
+        self.consume(&[TokenType::CloseSquare])?;
+        Ok(())
+    }
+
     pub(super) fn block(&mut self, _: bool) -> Result<(), CompileError> {
         self.emit_begin_scope();
         self.begin_scope();
-        while let Ok(_) = self.consume(&[TokenType::EndOfLine]) {}
+        self.skip_separators()?;
+
+        // Each statement's value is popped (running it, if it's a command)
+        // except the last, which becomes the value of the block.
+        let mut has_value = false;
+
+        // `{:}` is an empty map; `{}` is an empty block.
+        if self.match_type(TokenType::Colon)? {
+            self.emit_int_constant(0);
+            self.emit_op(Op::Map);
+            has_value = true;
+        }
+
         while !self.check(TokenType::CloseBrace) && !self.check(TokenType::EndOfFile) {
+            if has_value {
+                self.emit_op(Op::Pop);
+            }
             self.expression()?;
+
+            // If the first expression is followed by a `:`, it's the first
+            // key of a map literal.
+            if !has_value && self.check(TokenType::Colon) {
+                self.map_entries()?;
+                has_value = true;
+                break;
+            }
+
+            has_value = true;
+            self.skip_separators()?;
+        }
+
+        if !has_value {
+            self.emit_none();
         }
+
         self.consume(&[TokenType::CloseBrace])?;
         self.end_scope();
         self.emit_end_scope();
         Ok(())
     }
 
+    fn map_entries(&mut self) -> Result<(), CompileError> {
+        // { <key>: <expr>, <key>: <expr>, ... }, with the first key already
+        // compiled.
+        let mut count = 0;
+
+        loop {
+            self.consume(&[TokenType::Colon])?;
+            self.expression()?;
+            count += 1;
+            self.skip_separators()?;
+
+            if !self.match_type(TokenType::Comma)? {
+                break;
+            }
+
+            // A trailing comma is allowed.
+            self.skip_separators()?;
+            if self.check(TokenType::CloseBrace) {
+                break;
+            }
+
+            self.expression()?;
+        }
+
+        self.emit_int_constant(count);
+        self.emit_op(Op::Map);
+        Ok(())
+    }
+
+    pub(super) fn field(&mut self, _: bool) -> Result<(), CompileError> {
+        // <expr>.<name>
+        self.consume(&[TokenType::Identifier])?;
+        self.emit_string_constant(self.previous.text.to_owned());
+        self.emit_op(Op::Index);
+        Ok(())
+    }
+
+    /// Skips to the next line, for constructs that can be continued there.
+    fn skip_line_ends(&mut self) -> Result<(), CompileError> {
+        while self.match_type(TokenType::EndOfLine)? || self.match_type(TokenType::EndCommand)? {}
+        Ok(())
+    }
+
+    fn skip_separators(&mut self) -> Result<(), CompileError> {
+        while self.match_type(TokenType::EndOfLine)?
+            || self.match_type(TokenType::EndCommand)?
+            || self.match_type(TokenType::Semicolon)?
+        {}
+        Ok(())
+    }
+
     pub fn begin_scope(&mut self) {
         self.scope_depth += 1;
     }
@@ -146,27 +382,58 @@ impl Compiler {
 
     pub(super) fn command(&mut self, _: bool) -> Result<(), CompileError> {
         let command = self.previous.text.to_owned();
+        let (line, column) = (self.previous.line, self.previous.column);
         let mut count = 0;
 
-        loop {
-            self.expression()?;
-
-            if matches!(
-                self.previous.token_type,
-                TokenType::EndCommand | TokenType::EndOfFile | TokenType::EndOfLine
-            ) {
-                break;
-            }
-
+        // The token that ends the command is left for the caller, so that it
+        // can separate statements, close a group or block, or start a pipe.
+        while !self.at_end_of_command() && redirect_kind(self.current.token_type).is_none() {
+            // Arguments are parsed above the level of pipes and calls, so that
+            // `cmd @x (other)` passes two arguments rather than calling `@x`.
+            self.parse_precedence(Precedence::Primary as u8)?;
             count += 1;
         }
 
+        self.chunk.set_position(line, column);
         self.emit_int_constant(count);
         self.emit_command(command);
 
+        // Redirections follow the arguments and are applied in order.
+        while let Some(kind) = redirect_kind(self.current.token_type) {
+            self.advance()?;
+
+            if kind == RedirectKind::ErrorToOutput {
+                self.emit_none();
+            } else if self.at_end_of_command() {
+                return Err(CompileError::MissingToken(
+                    vec![TokenType::String],
+                    self.current.clone(),
+                ));
+            } else {
+                self.parse_precedence(Precedence::Primary as u8)?;
+            }
+
+            self.emit_redirect(kind);
+        }
+
         Ok(())
     }
 
+    fn at_end_of_command(&self) -> bool {
+        matches!(
+            self.current.token_type,
+            TokenType::EndCommand
+                | TokenType::EndOfFile
+                | TokenType::EndOfLine
+                | TokenType::Semicolon
+                | TokenType::CloseBracket
+                | TokenType::CloseBrace
+                | TokenType::Pipe
+                | TokenType::Ampersand
+                | TokenType::While
+        )
+    }
+
     pub(super) fn read(&mut self, _: bool) -> Result<(), CompileError> {
         // read <type>  (e.g. number, json, text)
         self.expression()?;
@@ -198,17 +465,15 @@ impl Compiler {
     }
 
     pub(super) fn int_constant(&mut self, _: bool) -> Result<(), CompileError> {
-        match self.previous.text.parse::<ivalue>() {
-            Ok(val) => self.emit_int_constant(val),
-            Err(_) => return Err(CompileError::ParseError()),
-        }
+        let val = parse_int(&self.previous.text)?;
+        self.emit_int_constant(val);
         Ok(())
     }
 
     pub(super) fn float_constant(&mut self, _: bool) -> Result<(), CompileError> {
         match self.previous.text.parse::<fvalue>() {
             Ok(val) => self.emit_float_constant(val),
-            Err(_) => return Err(CompileError::ParseError()),
+            Err(_) => return Err(CompileError::InvalidNumber(self.previous.text.to_owned())),
         }
         Ok(())
     }
@@ -250,26 +515,57 @@ impl Compiler {
         self.match_type(TokenType::EqualGreater)?;
 
         let offset = self.emit_branch(Op::Branch);
-        let arity = params.len() as u8;
+        let body_offset = self.current_offset();
+        let arity = u8::try_from(params.len()).map_err(|_| CompileError::TooManyArguments)?;
+
+        // A function body can't `break` out of a loop it's defined in.
+        let loops = std::mem::take(&mut self.loops);
 
         self.emit_begin_scope();
 
-        // Pull arguments from stack
-        for identifier in params {
-            self.emit_var(Op::DefineLocal, &identifier);
+        // Pull arguments from stack; the last argument is on top.
+        for identifier in params.iter().rev() {
+            self.emit_var(Op::DefineLocal, identifier);
+            self.emit_op(Op::Pop);
         }
 
         // Function body
+        self.skip_line_ends()?;
         self.expression()?;
 
         self.emit_end_scope();
         self.emit_return();
 
+        self.loops = loops;
+
         // Patch our branch.
         self.patch_branch(offset);
 
         // Now make the function
-        self.emit_function(Value::Function(arity, &self.chunk.content[offset]));
+        self.emit_function(arity, body_offset);
+
+        Ok(())
+    }
+
+    pub(super) fn call(&mut self, _: bool) -> Result<(), CompileError> {
+        // @function(<expr>, <expr>, ...)
+        let mut arg_count: u8 = 0;
+
+        if !self.check(TokenType::CloseBracket) {
+            loop {
+                self.expression()?;
+                arg_count = arg_count
+                    .checked_add(1)
+                    .ok_or(CompileError::TooManyArguments)?;
+
+                if !self.match_type(TokenType::Comma)? {
+                    break;
+                }
+            }
+        }
+
+        self.consume(&[TokenType::CloseBracket])?;
+        self.emit_call(arg_count);
 
         Ok(())
     }
@@ -340,6 +636,7 @@ impl Compiler {
         self.emit_op(Op::Pop);
 
         // <expr>
+        self.skip_line_ends()?;
         self.expression()?;
 
         let else_offset = self.emit_branch(Op::Branch);
@@ -351,6 +648,7 @@ impl Compiler {
 
         // else
         if self.match_type(TokenType::Else)? {
+            self.skip_line_ends()?;
             self.expression()?;
         } else {
             self.emit_none();
@@ -389,20 +687,191 @@ impl Compiler {
     }
 
     pub(super) fn while_(&mut self, _: bool) -> Result<(), CompileError> {
+        // while <cond> [do] <expr>
+        self.conditional_loop(false)
+    }
+
+    pub(super) fn until(&mut self, _: bool) -> Result<(), CompileError> {
+        // until <cond> [do] <expr>
+        self.conditional_loop(true)
+    }
+
+    fn conditional_loop(&mut self, until: bool) -> Result<(), CompileError> {
         let start_offset = self.current_offset();
 
         self.expression()?;
+        if until {
+            self.emit_op(Op::Not);
+        }
 
         let end_offset = self.emit_branch(Op::BranchIfFalse);
         self.emit_op(Op::Pop);
 
+        // As in a `for` loop, the body can start with `do`, which would
+        // otherwise begin a `do ... while` loop.
+        self.match_type(TokenType::Do)?;
+
+        self.begin_loop(Some(start_offset));
         self.expression()?;
+        self.emit_op(Op::Pop);
 
         self.emit_loop(start_offset);
 
         self.patch_branch(end_offset);
+        self.emit_op(Op::Pop);
+
+        // A `break` has already left the condition behind.
+        self.end_loop();
+        self.emit_none();
+        Ok(())
+    }
+
+    pub(super) fn do_while(&mut self, _: bool) -> Result<(), CompileError> {
+        // do <expr> while <cond>
+        let start_offset = self.current_offset();
+
+        self.begin_loop(None);
+        self.expression()?;
+        self.emit_op(Op::Pop);
+
+        self.consume(&[TokenType::While])?;
+
+        let continues = std::mem::take(&mut self.loops.last_mut().unwrap().continues);
+        for offset in continues {
+            self.patch_branch(offset);
+        }
+
+        self.expression()?;
+
+        let end_offset = self.emit_branch(Op::BranchIfFalse);
+        self.emit_op(Op::Pop);
+        self.emit_loop(start_offset);
+
+        self.patch_branch(end_offset);
+        self.emit_op(Op::Pop);
+
+        self.end_loop();
+        self.emit_none();
+        Ok(())
+    }
+
+    fn begin_loop(&mut self, start_offset: Option<usize>) {
+        self.loops.push(Loop {
+            start_offset,
+            scope_depth: self.scope_depth,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+    }
+
+    /// Points each `break` in the innermost loop at the current offset.
+    fn end_loop(&mut self) {
+        if let Some(finished) = self.loops.pop() {
+            for offset in finished.breaks {
+                self.patch_branch(offset);
+            }
+        }
+    }
 
+    /// Closes the scopes opened since the innermost loop began, returning
+    /// that loop's index.
+    fn exit_loop_scopes(&mut self, error: CompileError) -> Result<usize, CompileError> {
+        let index = self.loops.len().checked_sub(1).ok_or(error)?;
+
+        for _ in self.loops[index].scope_depth..self.scope_depth {
+            self.emit_end_scope();
+        }
+
+        Ok(index)
+    }
+
+    pub(super) fn break_(&mut self, _: bool) -> Result<(), CompileError> {
+        let index = self.exit_loop_scopes(CompileError::BreakOutsideLoop)?;
+        let offset = self.emit_branch(Op::Branch);
+        self.loops[index].breaks.push(offset);
+        Ok(())
+    }
+
+    pub(super) fn continue_(&mut self, _: bool) -> Result<(), CompileError> {
+        let index = self.exit_loop_scopes(CompileError::ContinueOutsideLoop)?;
+        match self.loops[index].start_offset {
+            Some(start_offset) => self.emit_loop(start_offset),
+            None => {
+                let offset = self.emit_branch(Op::Branch);
+                self.loops[index].continues.push(offset);
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn for_(&mut self, _: bool) -> Result<(), CompileError> {
+        // for @x in <item> <item> ... do <expr>
+        self.consume(&[TokenType::LocalVariable])?;
+        let identifier = self.previous.text.to_owned();
+        self.consume(&[TokenType::In])?;
+
+        let mut count = 0;
+        while !self.check(TokenType::Do) && !self.at_end_of_command() {
+            // Globs are only expanded here; as a command argument, a pattern
+            // is passed on as it was written.
+            if self.match_type(TokenType::Glob)? {
+                self.emit_string_constant(self.previous.text.to_owned());
+                self.emit_op(Op::Glob);
+            } else {
+                self.parse_precedence(Precedence::Primary as u8)?;
+
+                // A range whose bounds aren't both written as numbers, e.g.
+                // `1..@n`.
+                if self.match_type(TokenType::DotDot)? {
+                    self.parse_precedence(Precedence::Primary as u8)?;
+                    self.emit_op(Op::Range);
+                }
+            }
+            count += 1;
+        }
+
+        self.consume(&[TokenType::Do])?;
+
+        self.emit_int_constant(count);
+        self.emit_op(Op::Iterate);
+
+        let start_offset = self.current_offset();
+        let end_offset = self.emit_branch(Op::IterateNext);
+        self.begin_loop(Some(start_offset));
+
+        // Each item gets a fresh scope.
+        self.emit_begin_scope();
+        self.begin_scope();
+        self.emit_var(Op::DefineLocal, &identifier);
         self.emit_op(Op::Pop);
+
+        self.expression()?;
+        self.emit_op(Op::Pop);
+        self.end_scope();
+        self.emit_end_scope();
+
+        self.emit_loop(start_offset);
+        self.patch_branch(end_offset);
+
+        self.end_loop();
+        self.emit_op(Op::IterateEnd);
+        self.emit_none();
+        Ok(())
+    }
+
+    pub(super) fn range(&mut self, _: bool) -> Result<(), CompileError> {
+        // <from>..<to> written as a single word, e.g. `1..10`
+        let text = self.previous.text.to_owned();
+        let (from, to) = text
+            .split_once("..")
+            .ok_or_else(|| CompileError::InvalidNumber(text.to_owned()))?;
+
+        for bound in [from, to] {
+            let val = parse_int(bound)?;
+            self.emit_int_constant(val);
+        }
+
+        self.emit_op(Op::Range);
         Ok(())
     }
 
@@ -415,24 +884,46 @@ impl Compiler {
 
     pub fn compile_to(&mut self, tt: TokenType) -> Result<(), CompileError> {
         self.advance()?;
+        self.skip_separators()?;
 
+        // As in a block, every statement but the last is popped, and the last
+        // is returned.
+        let mut has_value = false;
         while !self.match_type(tt)? {
-            while let Ok(_) = self.consume(&[TokenType::EndOfLine]) {}
-            self.expression()?;
-            self.consume(&[
-                TokenType::EndOfLine,
-                TokenType::EndOfFile,
-                TokenType::Semicolon,
-                tt,
-            ])?;
-            if !self.check(tt) {
+            if has_value {
                 self.emit_op(Op::Pop);
             }
+            self.expression()?;
+            has_value = true;
+
+            // Some statements (e.g. an `if` looking for an `else`) will have
+            // already consumed the separator that ends them, and a command run
+            // in the background needs none.
+            if !self.check(tt)
+                && !matches!(
+                    self.previous.token_type,
+                    TokenType::EndOfLine
+                        | TokenType::EndCommand
+                        | TokenType::Semicolon
+                        | TokenType::Ampersand
+                )
+            {
+                self.consume(&[
+                    TokenType::EndOfLine,
+                    TokenType::EndCommand,
+                    TokenType::Semicolon,
+                ])?;
+            }
+            self.skip_separators()?;
+        }
+
+        if !has_value {
+            self.emit_none();
         }
         self.emit_return();
 
         if self.debug_output_chunk {
-            println!("{}", self.chunk.display());
+            eprintln!("{}", self.chunk.display());
         }
 
         Ok(())
@@ -444,3 +935,67 @@ impl Into<Scanner> for Compiler {
         self.scanner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Result<(), CompileError> {
+        let mut compiler = Compiler::new(Scanner::new(source.to_owned()), ByteCodeChunk::new());
+        compiler.compile_to(TokenType::EndOfFile)
+    }
+
+    fn call_with(count: usize) -> String {
+        format!("@f({})", vec!["1"; count].join(", "))
+    }
+
+    #[test]
+    fn call_takes_up_to_255_arguments() {
+        assert!(compile(&call_with(255)).is_ok());
+    }
+
+    #[test]
+    fn call_with_256_arguments_is_an_error() {
+        assert!(matches!(
+            compile(&call_with(256)),
+            Err(CompileError::TooManyArguments)
+        ));
+    }
+
+    #[test]
+    fn function_with_256_parameters_is_an_error() {
+        let params: Vec<String> = (0..256).map(|i| format!("@p{}", i)).collect();
+        let source = format!("let @f {} => 0", params.join(" "));
+        assert!(matches!(
+            compile(&source),
+            Err(CompileError::TooManyArguments)
+        ));
+    }
+
+    #[test]
+    fn int_out_of_range_is_reported_as_such() {
+        assert!(matches!(
+            compile("echo (2147483648)"),
+            Err(CompileError::IntOutOfRange(text)) if text == "2147483648"
+        ));
+        assert!(matches!(
+            compile("echo 1..99999999999"),
+            Err(CompileError::IntOutOfRange(text)) if text == "99999999999"
+        ));
+        assert!(compile("echo (2147483647)").is_ok());
+    }
+
+    #[test]
+    fn parse_int_tells_overflow_from_other_failures() {
+        assert!(matches!(parse_int("-2147483648"), Ok(ivalue::MIN)));
+        assert!(matches!(
+            parse_int("-2147483649"),
+            Err(CompileError::IntOutOfRange(_))
+        ));
+        assert!(matches!(
+            parse_int("1x"),
+            Err(CompileError::InvalidNumber(_))
+        ));
+        assert!(matches!(parse_int(""), Err(CompileError::InvalidNumber(_))));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::vm::op::Op;
-use crate::vm::value::{fvalue, ivalue, Value, IVALUE_SIZE};
+use crate::vm::redirect::RedirectKind;
+use crate::vm::value::{fvalue, ivalue};
 
 use super::compiler::Compiler;
 
@@ -11,7 +12,7 @@ impl Compiler {
 
     #[inline(always)]
     pub(super) fn emit_op(&mut self, v: Op) {
-        self.emit_bytes(&[v as u8])
+        self.chunk.write_op(v)
     }
 
     #[inline(always)]
@@ -74,6 +75,18 @@ impl Compiler {
         self.chunk.write_usize(constant_id);
     }
 
+    #[inline(always)]
+    pub(super) fn emit_redirect(&mut self, kind: RedirectKind) {
+        self.chunk.write_op(Op::Redirect);
+        self.emit_bytes(&[kind as u8]);
+    }
+
+    #[inline(always)]
+    pub(super) fn emit_call(&mut self, arg_count: u8) {
+        self.chunk.write_op(Op::Call);
+        self.emit_bytes(&[arg_count]);
+    }
+
     #[inline(always)]
     pub(super) fn emit_sys_call(&mut self, call: String) {
         let constant_id = self.chunk.add_string(&call);
@@ -105,14 +118,14 @@ impl Compiler {
     pub(super) fn emit_loop(&mut self, start_offset: usize) {
         self.chunk.write_op(Op::BranchBack);
 
-        let offset = self.chunk.len() - start_offset + IVALUE_SIZE;
+        let offset = self.chunk.len() - start_offset + size_of::<usize>();
 
         self.chunk.write_usize(offset);
     }
 
     #[inline(always)]
-    pub(super) fn emit_function(&mut self, func: Value) {
-        let id = self.chunk.add_function(func);
+    pub(super) fn emit_function(&mut self, arity: u8, offset: usize) {
+        let id = self.chunk.add_function(arity, offset);
         self.chunk.write_op(Op::FunctionDefinition);
         self.chunk.write_usize(id);
     }
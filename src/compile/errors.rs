@@ -1,6 +1,12 @@
-use crate::scan::{
-    errors::ScanError,
-    tokens::{Token, TokenType},
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    diagnostic::Diagnostic,
+    scan::{
+        errors::ScanError,
+        tokens::{Token, TokenType},
+    },
+    vm::value::ivalue,
 };
 
 #[derive(Debug)]
@@ -8,9 +14,107 @@ pub enum CompileError {
     Unknown,
     NotImplemented,
     MissingToken(Vec<TokenType>, Token),
-    UnknownUnaryOperator,
+    /// The input ended where an expression was needed, e.g. after `then`.
+    MissingExpression(Token),
+    UnknownUnaryOperator(TokenType),
     MissingFrom,
-    ParseError(),
+    InvalidNumber(String),
+    /// An int literal too large or too small to fit in an int.
+    IntOutOfRange(String),
     ScanError(ScanError),
     InvalidAssignment,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    /// A function defined or called with more arguments than a `Call` can
+    /// pass.
+    TooManyArguments,
+}
+
+/// Describes a token that was found, quoting its text where it has any.
+fn describe_found(token: &Token) -> String {
+    match token.token_type {
+        TokenType::EndOfFile | TokenType::EndOfLine | TokenType::EndCommand => {
+            token.token_type.describe().to_owned()
+        }
+        _ if !token.text.trim().is_empty() => format!("`{}`", token.text),
+        other => other.describe().to_owned(),
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Unknown => write!(f, "unknown error"),
+            CompileError::NotImplemented => write!(f, "not implemented yet"),
+            CompileError::MissingToken(expected, found) => {
+                let mut expected: Vec<&str> = expected.iter().map(|t| t.describe()).collect();
+                let last = expected.pop().unwrap_or("something else");
+                let expected = match expected.is_empty() {
+                    true => last.to_owned(),
+                    false => format!("{} or {}", expected.join(", "), last),
+                };
+                write!(
+                    f,
+                    "expected {} but found {}",
+                    expected,
+                    describe_found(found)
+                )
+            }
+            CompileError::MissingExpression(found) => {
+                write!(
+                    f,
+                    "expected an expression but found {}",
+                    describe_found(found)
+                )
+            }
+            CompileError::UnknownUnaryOperator(token_type) => {
+                write!(f, "{} is not a unary operator", token_type.describe())
+            }
+            CompileError::MissingFrom => write!(f, "expected `from`"),
+            CompileError::InvalidNumber(text) => write!(f, "invalid number `{}`", text),
+            CompileError::IntOutOfRange(text) => write!(f, "`{}` is out of range for an int", text),
+            CompileError::ScanError(e) => write!(f, "{}", e),
+            CompileError::InvalidAssignment => write!(f, "invalid assignment target"),
+            CompileError::BreakOutsideLoop => write!(f, "`break` outside of a loop"),
+            CompileError::ContinueOutsideLoop => write!(f, "`continue` outside of a loop"),
+            CompileError::TooManyArguments => {
+                write!(f, "a function can't take more than {} arguments", u8::MAX)
+            }
+        }
+    }
+}
+
+impl CompileError {
+    /// Whether the input ended too soon, so that more of it could make it
+    /// valid, e.g. a `{` without its `}` or a string without its closing `"`.
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            CompileError::MissingToken(_, found) | CompileError::MissingExpression(found) => {
+                found.token_type == TokenType::EndOfFile
+            }
+            CompileError::ScanError(ScanError::MissingStringDelimiter) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Diagnostic for CompileError {
+    fn hint(&self) -> Option<String> {
+        match self {
+            CompileError::MissingFrom => Some("e.g. `read number from console`".to_owned()),
+            CompileError::IntOutOfRange(_) => Some(format!(
+                "ints must be between {} and {}",
+                ivalue::MIN,
+                ivalue::MAX
+            )),
+            CompileError::ScanError(e) => e.hint(),
+            CompileError::InvalidAssignment => {
+                Some("only variables such as `@x` can be assigned to".to_owned())
+            }
+            CompileError::BreakOutsideLoop | CompileError::ContinueOutsideLoop => {
+                Some("it can only be used in a `for`, `while`, `until` or `do` loop".to_owned())
+            }
+            _ => None,
+        }
+    }
 }
@@ -11,9 +11,11 @@ pub enum Precedence {
     And,
     Equality,
     Comparison,
+    Range,
     Term,
     Factor,
     Unary,
+    Pipe,
     Call,
     Primary,
 
@@ -29,9 +31,11 @@ impl Precedence {
             x if x == Precedence::And as u8 => Precedence::And,
             x if x == Precedence::Equality as u8 => Precedence::Equality,
             x if x == Precedence::Comparison as u8 => Precedence::Comparison,
+            x if x == Precedence::Range as u8 => Precedence::Range,
             x if x == Precedence::Term as u8 => Precedence::Term,
             x if x == Precedence::Factor as u8 => Precedence::Factor,
             x if x == Precedence::Unary as u8 => Precedence::Unary,
+            x if x == Precedence::Pipe as u8 => Precedence::Pipe,
             x if x == Precedence::Call as u8 => Precedence::Call,
             x if x == Precedence::Primary as u8 => Precedence::Primary,
             _ => Precedence::Invalid,
@@ -47,8 +51,14 @@ impl ParseRule {
     pub(super) fn prefix_for(token_type: TokenType) -> Option<ParseFn> {
         match token_type {
             TokenType::OpenBracket => Some(Compiler::grouping),
-            TokenType::Minus => Some(Compiler::unary),
-            TokenType::Identifier | TokenType::String => Some(Compiler::string_constant),
+            TokenType::Minus | TokenType::Bang | TokenType::Not | TokenType::Len => {
+                Some(Compiler::unary)
+            }
+            TokenType::OpenSquare => Some(Compiler::list),
+            TokenType::Identifier | TokenType::String | TokenType::Glob => {
+                Some(Compiler::string_constant)
+            }
+            TokenType::Range => Some(Compiler::range),
             TokenType::Int => Some(Compiler::int_constant),
             TokenType::Float => Some(Compiler::float_constant),
             TokenType::Command => Some(Compiler::command),
@@ -62,6 +72,11 @@ impl ParseRule {
             TokenType::Pin => Some(Compiler::pin_var),
             TokenType::If => Some(Compiler::if_),
             TokenType::While => Some(Compiler::while_),
+            TokenType::Until => Some(Compiler::until),
+            TokenType::Do => Some(Compiler::do_while),
+            TokenType::For => Some(Compiler::for_),
+            TokenType::Break => Some(Compiler::break_),
+            TokenType::Continue => Some(Compiler::continue_),
             _ => None,
         }
     }
@@ -73,9 +88,20 @@ impl ParseRule {
             TokenType::Slash => Some(Compiler::binary),
             TokenType::Star => Some(Compiler::binary),
             TokenType::Pipe => Some(Compiler::binary),
+            TokenType::Ampersand => Some(Compiler::background),
             TokenType::And => Some(Compiler::and),
             TokenType::Or => Some(Compiler::or),
-            TokenType::EqualEqual => Some(Compiler::binary),
+            TokenType::EqualEqual | TokenType::BangEqual => Some(Compiler::binary),
+            TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::LessEqualGreater => Some(Compiler::binary),
+            TokenType::DotDot => Some(Compiler::binary),
+            TokenType::OpenBracket => Some(Compiler::call),
+            TokenType::OpenSquare => Some(Compiler::index),
+            TokenType::Dot => Some(Compiler::field),
+            TokenType::In => Some(Compiler::binary),
             _ => None,
         }
     }
@@ -85,10 +111,22 @@ impl ParseRule {
             TokenType::Minus | TokenType::Plus => Precedence::Term,
             TokenType::Slash | TokenType::Star => Precedence::Factor,
             TokenType::Equal | TokenType::QuestionEqual => Precedence::Assignment,
-            TokenType::Pipe => Precedence::Primary,
+            // A trailing `&` applies to the whole pipeline before it.
+            TokenType::Ampersand => Precedence::Assignment,
+            TokenType::Pipe => Precedence::Pipe,
             TokenType::And => Precedence::And,
             TokenType::Or => Precedence::Or,
-            TokenType::EqualEqual => Precedence::Equality,
+            TokenType::EqualEqual | TokenType::BangEqual => Precedence::Equality,
+            TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::LessEqualGreater
+            | TokenType::In => Precedence::Comparison,
+            TokenType::DotDot => Precedence::Range,
+            TokenType::OpenBracket => Precedence::Call,
+            // Indexing and fields bind tightest, so that it applies to command arguments.
+            TokenType::OpenSquare | TokenType::Dot => Precedence::Primary,
             _ => Precedence::None,
         }
     }
@@ -102,16 +140,37 @@ impl Compiler {
 
         if let Some(prefix) = ParseRule::prefix_for(self.previous.token_type) {
             prefix(self, can_assign)?;
+        } else if self.previous.token_type == TokenType::EndOfFile {
+            // Something must follow, e.g. the body of an `if` after `then`.
+            return Err(CompileError::MissingExpression(self.previous.clone()));
         } else {
             return Ok(());
         }
 
         loop {
+            // An expression never continues past the end of its statement,
+            // even if the prefix (e.g. an `if` looking for `else`) read past it.
+            if matches!(
+                self.previous.token_type,
+                TokenType::EndOfLine | TokenType::EndCommand | TokenType::Semicolon
+            ) {
+                break;
+            }
+
             let infix_precedence = ParseRule::precedence_for(self.current.token_type);
             if precedence > infix_precedence as u8 {
                 break;
             }
 
+            // A word, e.g. a `for` item, isn't indexed by a list after a
+            // space, as in `for @x in @xs [1, 2]`.
+            if precedence == Precedence::Primary as u8
+                && self.current.token_type == TokenType::OpenSquare
+                && self.current.start > self.previous.end
+            {
+                break;
+            }
+
             self.advance()?;
             if let Some(infix) = ParseRule::infix_for(self.previous.token_type) {
                 infix(self, can_assign)?;
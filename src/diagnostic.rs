@@ -0,0 +1,178 @@
+use std::fmt::Display;
+
+use crate::scan::tokens::Token;
+
+/// An error that can be shown to the user. The message comes from `Display`,
+/// and may be followed by a hint on how to fix it.
+pub trait Diagnostic: Display {
+    fn hint(&self) -> Option<String> {
+        None
+    }
+}
+
+/// A span of source text, starting at a line and column (both from 1).
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub line: i64,
+    pub column: i64,
+    pub length: usize,
+}
+
+impl Location {
+    pub fn of(token: &Token) -> Self {
+        Location {
+            line: token.line,
+            column: token.column,
+            length: (token.end - token.start).max(1) as usize,
+        }
+    }
+}
+
+/// Formats an error with the file and position it came from, followed by the
+/// offending line of source with the location underlined, e.g.
+///
+/// ```text
+/// error: invalid assignment target
+///  --> script.insh:2:12
+///   |
+/// 2 | let @x = 1 = 2
+///   |            ^
+///   = hint: only variables such as `@x` can be assigned to
+/// ```
+pub fn render(file: &str, source: &str, location: &Location, error: &dyn Diagnostic) -> String {
+    // The end of the file is reported after the end of the last line.
+    let lines: Vec<&str> = source.lines().collect();
+    let (line, column) = match usize::try_from(location.line - 1) {
+        Ok(i) if i < lines.len() => (location.line, location.column),
+        _ => match lines.last() {
+            Some(last) => (lines.len() as i64, last.chars().count() as i64 + 1),
+            None => (location.line, location.column),
+        },
+    };
+
+    let mut output = format!("error: {}\n --> {}:{}:{}\n", error, file, line, column);
+    let gutter = " ".repeat(line.to_string().len());
+
+    let Some(text) = usize::try_from(line - 1).ok().and_then(|i| lines.get(i)) else {
+        return output + &render_hint(&gutter, error);
+    };
+
+    // Keep any tabs before the caret, so that it lines up.
+    let indent: String = text
+        .chars()
+        .take((column - 1).max(0) as usize)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    // Don't underline past the end of the line.
+    let remaining = text.chars().count().saturating_sub(indent.chars().count());
+    let length = location.length.min(remaining).max(1);
+
+    output += &format!("{} |\n", gutter);
+    output += &format!("{} | {}\n", line, text);
+    output += &format!("{} | {}{}\n", gutter, indent, "^".repeat(length));
+    output + &render_hint(&gutter, error)
+}
+
+/// Formats an error that has no position, with its hint if it has one.
+pub fn render_plain(error: &dyn Diagnostic) -> String {
+    format!("error: {}\n", error) + &render_hint(" ", error)
+}
+
+fn render_hint(gutter: &str, error: &dyn Diagnostic) -> String {
+    match error.hint() {
+        Some(hint) => format!("{} = hint: {}\n", gutter, hint),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use super::*;
+
+    struct TestError(Option<&'static str>);
+
+    impl Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "bad")
+        }
+    }
+
+    impl Diagnostic for TestError {
+        fn hint(&self) -> Option<String> {
+            self.0.map(str::to_owned)
+        }
+    }
+
+    fn at(line: i64, column: i64, length: usize) -> Location {
+        Location {
+            line,
+            column,
+            length,
+        }
+    }
+
+    fn render_at(source: &str, location: Location) -> String {
+        render("test", source, &location, &TestError(None))
+    }
+
+    #[test]
+    fn caret_underlines_the_location() {
+        assert_eq!(
+            render_at("let @x = 1 = 2", at(1, 12, 1)),
+            "error: bad\n --> test:1:12\n  |\n1 | let @x = 1 = 2\n  |            ^\n"
+        );
+        assert_eq!(
+            render_at("echo one\necho two", at(2, 6, 3)),
+            "error: bad\n --> test:2:6\n  |\n2 | echo two\n  |      ^^^\n"
+        );
+    }
+
+    #[test]
+    fn caret_stops_at_the_end_of_the_line() {
+        assert_eq!(
+            render_at("echo ab", at(1, 6, 10)),
+            "error: bad\n --> test:1:6\n  |\n1 | echo ab\n  |      ^^\n"
+        );
+    }
+
+    #[test]
+    fn end_of_file_points_after_the_last_line() {
+        assert_eq!(
+            render_at("echo (\n", at(2, 1, 1)),
+            "error: bad\n --> test:1:7\n  |\n1 | echo (\n  |       ^\n"
+        );
+        assert_eq!(render_at("", at(1, 1, 1)), "error: bad\n --> test:1:1\n");
+    }
+
+    #[test]
+    fn tabs_before_the_caret_are_kept() {
+        assert_eq!(
+            render_at("\tif\t@x", at(1, 5, 2)),
+            "error: bad\n --> test:1:5\n  |\n1 | \tif\t@x\n  | \t  \t^^\n"
+        );
+    }
+
+    #[test]
+    fn multi_byte_text_is_counted_in_characters() {
+        assert_eq!(
+            render_at("echo héllo wörld", at(1, 12, 5)),
+            "error: bad\n --> test:1:12\n  |\n1 | echo héllo wörld\n  |            ^^^^^\n"
+        );
+        assert_eq!(
+            render_at("é = ü", at(1, 3, 1)),
+            "error: bad\n --> test:1:3\n  |\n1 | é = ü\n  |   ^\n"
+        );
+    }
+
+    #[test]
+    fn gutter_is_as_wide_as_the_line_number() {
+        let source = "\n".repeat(9) + "echo x";
+        assert_eq!(
+            render("test", &source, &at(10, 1, 4), &TestError(Some("try this"))),
+            "error: bad\n --> test:10:1\n   |\n10 | echo x\n   | ^^^^\n   = hint: try this\n"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+
+mod completion;
+mod highlight;
+
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Helper, Result,
+};
+
+use crate::{
+    compile::compiler::Compiler,
+    scan::{scanner::Scanner, tokens::TokenType},
+    vm::{chunk::bytecode_chunk::ByteCodeChunk, vm::Vm},
+};
+
+use completion::Completions;
+
+/// Hooks the shell into the line editor used at the prompt.
+#[derive(Default)]
+pub struct ShellHelper {
+    completions: Completions,
+}
+
+impl ShellHelper {
+    /// Catches up with the builtins, locals and completion specs defined by
+    /// the commands run so far, and the executables now in `$PATH`.
+    pub fn update(&mut self, vm: &Vm) {
+        self.completions.update(vm);
+    }
+}
+
+impl Helper for ShellHelper {}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    /// Offers commands, keywords, paths, `@locals` or `$VARS`, depending on
+    /// where the word being completed is.
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+        Ok(self.completions.complete(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight::highlight(line, |name| {
+            self.completions.is_command(name)
+        }))
+    }
+
+    /// Colours change as the line is typed, e.g. a command turns from red to
+    /// green once its name is complete.
+    fn highlight_char(&self, _: &str, _: usize, _: bool) -> bool {
+        true
+    }
+}
+
+impl Validator for ShellHelper {
+    /// Keeps reading lines while the input so far is unfinished, e.g. an
+    /// unclosed `{` or string, or a `then` with nothing after it. Any other
+    /// error is left for the shell to report once the line is entered.
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        let mut compiler =
+            Compiler::new(Scanner::new(ctx.input().to_owned()), ByteCodeChunk::new());
+
+        match compiler.compile_to(TokenType::EndOfFile) {
+            Err(e) if e.is_incomplete() => Ok(ValidationResult::Incomplete),
+            _ => Ok(ValidationResult::Valid(None)),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
+    fs,
+    path::Path,
+};
+
+use rustyline::completion::Pair;
+
+use crate::{
+    scan::{
+        scanner::{Scanner, ScannerMode, KEYWORDS},
+        tokens::TokenType,
+    },
+    vm::{
+        builtins::{complete::CompletionSpec, shell::is_executable},
+        vm::Vm,
+    },
+};
+
+/// What the shell knows about that Tab can offer, copied from the `Vm`
+/// before each prompt.
+#[derive(Default)]
+pub struct Completions {
+    builtins: Vec<String>,
+    functions: Vec<String>,
+    locals: Vec<String>,
+    specs: HashMap<String, CompletionSpec>,
+    /// The names of the executables in `$PATH`, so that they aren't searched
+    /// for on every keystroke.
+    executables: HashSet<String>,
+    /// The `$PATH` that `executables` was found in, or none before the first
+    /// search. They're only searched for again when it changes.
+    path: Option<OsString>,
+}
+
+/// Characters that end one word and start another, besides whitespace.
+const WORD_BREAKS: [char; 7] = [';', '|', '&', '(', ')', '{', '}'];
+
+impl Completions {
+    pub fn update(&mut self, vm: &Vm) {
+        self.builtins = vm.builtin_names();
+        self.functions = vm.function_names();
+        self.locals = vm.local_names();
+        self.specs = vm.completion_specs().clone();
+
+        let path = env::var_os("PATH").unwrap_or_default();
+        if self.path.as_ref() != Some(&path) {
+            self.executables = executables(&path);
+            self.path = Some(path);
+        }
+    }
+
+    /// Whether `name` runs a function, builtin or executable.
+    pub fn is_command(&self, name: &str) -> bool {
+        if name.contains('/') {
+            return is_executable(Path::new(name));
+        }
+
+        self.functions.iter().any(|function| function == name)
+            || self.builtins.iter().any(|builtin| builtin == name)
+            || self.executables.contains(name)
+    }
+
+    /// The start of the word at `pos` and what it could be completed to.
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || WORD_BREAKS.contains(&c))
+            .map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+
+        let mut candidates = if let Some(name) = word.strip_prefix('@') {
+            prefixed('@', self.locals.iter().cloned(), name)
+        } else if let Some(name) = word.strip_prefix('$') {
+            let names = env::vars_os().filter_map(|(name, _)| name.into_string().ok());
+            prefixed('$', names, name)
+        } else {
+            let (mode, command) = context(&line[..start]);
+            match mode {
+                ScannerMode::Command if !word.contains('/') => {
+                    let mut candidates = self.commands(word);
+                    candidates.extend(keywords(word));
+                    candidates
+                }
+                ScannerMode::Expression => keywords(word),
+                _ => match command.and_then(|command| self.specs.get(&command)) {
+                    Some(CompletionSpec::Words(words)) => words
+                        .iter()
+                        .filter(|w| w.starts_with(word))
+                        .map(|w| pair(w.clone(), format!("{} ", w)))
+                        .collect(),
+                    Some(CompletionSpec::Directories) => paths(word, true),
+                    Some(CompletionSpec::Commands) => self.commands(word),
+                    Some(CompletionSpec::Files) | None => paths(word, false),
+                },
+            }
+        };
+
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.display == b.display);
+        (start, candidates)
+    }
+
+    /// Functions, builtins and the executables in `$PATH` whose names start
+    /// with `prefix`.
+    fn commands(&self, prefix: &str) -> Vec<Pair> {
+        self.functions
+            .iter()
+            .chain(&self.builtins)
+            .chain(&self.executables)
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| pair(name.clone(), format!("{} ", name)))
+            .collect()
+    }
+}
+
+/// The names of the executables in the directories in `path`.
+fn executables(path: &OsString) -> HashSet<String> {
+    let mut names = HashSet::new();
+
+    for dir in env::split_paths(path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if is_executable(&entry.path()) {
+                names.insert(name);
+            }
+        }
+    }
+
+    names
+}
+
+/// How the scanner would read the next word after `text`, and the command
+/// it's an argument of, if any.
+fn context(text: &str) -> (ScannerMode, Option<String>) {
+    let mut scanner = Scanner::new(text.to_owned());
+
+    let mut command = None;
+    while let Ok(token) = scanner.read_token() {
+        match token.token_type {
+            TokenType::EndOfFile => break,
+            TokenType::Command => command = Some(token.text),
+            _ => {}
+        }
+    }
+
+    (scanner.mode(), command)
+}
+
+fn pair(display: String, replacement: String) -> Pair {
+    Pair {
+        display,
+        replacement,
+    }
+}
+
+fn prefixed(sigil: char, names: impl Iterator<Item = String>, prefix: &str) -> Vec<Pair> {
+    names
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| {
+            let word = format!("{}{}", sigil, name);
+            pair(word.clone(), word)
+        })
+        .collect()
+}
+
+fn keywords(prefix: &str) -> Vec<Pair> {
+    KEYWORDS
+        .iter()
+        .filter(|keyword| keyword.starts_with(prefix))
+        .map(|keyword| pair(keyword.to_string(), format!("{} ", keyword)))
+        .collect()
+}
+
+/// The files, or just the directories, whose paths start with `word`.
+/// Hidden files are only offered once a `.` has been typed.
+fn paths(word: &str, directories_only: bool) -> Vec<Pair> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            continue;
+        }
+
+        // A directory is completed with a `/`, ready for what's inside it.
+        if entry.path().is_dir() {
+            candidates.push(pair(format!("{}/", name), format!("{}{}/", dir, name)));
+        } else if !directories_only {
+            candidates.push(pair(name.clone(), format!("{}{} ", dir, name)));
+        }
+    }
+    candidates
+}
@@ -0,0 +1,80 @@
+use std::iter;
+
+use crate::scan::{scanner::Scanner, tokens::TokenType};
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[1;34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+
+/// Colours a line as the scanner reads it: commands green, or red if
+/// `is_command` doesn't know them, quoted strings yellow, `@locals` cyan,
+/// `$VARS` magenta and keywords blue. Arguments are left as they are.
+pub fn highlight(line: &str, is_command: impl Fn(&str) -> bool) -> String {
+    // Tokens count characters, and the line is indexed by byte.
+    let offsets: Vec<usize> = line
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(iter::once(line.len()))
+        .collect();
+
+    let mut text = String::with_capacity(line.len());
+    let mut end = 0;
+
+    for token in Scanner::tokenize(line) {
+        let (Some(&from), Some(&to)) = (
+            offsets.get(token.start as usize),
+            offsets.get(token.end as usize),
+        ) else {
+            continue;
+        };
+        if from < end || from == to {
+            continue;
+        }
+
+        let span = &line[from..to];
+        let colour = match token.token_type {
+            TokenType::Command if is_command(&token.text) => Some(GREEN),
+            TokenType::Command | TokenType::Unknown => Some(RED),
+            TokenType::String if span.starts_with('"') => Some(YELLOW),
+            TokenType::LocalVariable => Some(CYAN),
+            TokenType::EnvironmentVariable => Some(MAGENTA),
+            TokenType::Let
+            | TokenType::Pin
+            | TokenType::Pinned
+            | TokenType::Is
+            | TokenType::True
+            | TokenType::False
+            | TokenType::If
+            | TokenType::Then
+            | TokenType::Else
+            | TokenType::While
+            | TokenType::Until
+            | TokenType::Do
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::And
+            | TokenType::Or
+            | TokenType::Not
+            | TokenType::Len
+            | TokenType::For
+            | TokenType::In
+            | TokenType::Read
+            | TokenType::From => Some(BLUE),
+            _ => None,
+        };
+
+        text += &line[end..from];
+        match colour {
+            Some(colour) => text += &format!("{}{}{}", colour, span, RESET),
+            None => text += span,
+        }
+        end = to;
+    }
+
+    text += &line[end..];
+    text
+}
@@ -1,57 +1,100 @@
 use std::{
     env,
     fs::{self, OpenOptions},
+    io::{self, IsTerminal},
 };
 
-use rustyline::{error::ReadlineError, DefaultEditor, Result};
+use rustyline::{
+    error::ReadlineError, history::DefaultHistory, CompletionType, Config, Editor, Result,
+};
 
 use crate::{
-    compile::compiler::Compiler,
-    scan::{scanner::Scanner, tokens::TokenType},
-    vm::{chunk::bytecode_chunk::ByteCodeChunk, evaluate::EvaluateContext, vm::Vm},
+    diagnostic,
+    editor::ShellHelper,
+    scan::tokens::TokenType,
+    script::{report_runtime_error, STDIN},
+    vm::{
+        evaluate::EvaluateContext,
+        signals,
+        value::ivalue,
+        vm::{Vm, VmError},
+    },
 };
 
-pub fn interactive() -> Result<()> {
-    let mut editor = DefaultEditor::new()?;
+/// How many lines of history are kept, unless `$HISTSIZE` says otherwise.
+const HISTORY_SIZE: usize = 1000;
+
+/// Runs commands typed at a prompt until `exit` or Ctrl-D, returning the
+/// status to exit the shell with.
+pub fn interactive(vm: &mut Vm) -> Result<ivalue> {
+    let history_size = env::var("HISTSIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(HISTORY_SIZE);
+
+    // Lines starting with a space are left out of the history, as are
+    // repeats of the line before.
+    let config = Config::builder()
+        .max_history_size(history_size)?
+        .history_ignore_dups(true)?
+        .history_ignore_space(true)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(ShellHelper::default()));
+
+    #[cfg(feature = "with-file-history")]
+    let history = dirs::preference_dir().map(|dir| dir.join("insh/history"));
 
     #[cfg(feature = "with-file-history")]
-    if let Some(dir) = dirs::preference_dir() {
-        let path = dir.as_path().join("insh/history");
+    if let Some(path) = &history {
         if !path.exists() {
             if let Some(p) = path.parent() {
                 fs::create_dir_all(p)?
             }
-            let _ = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .open(path.as_path());
+            let _ = OpenOptions::new().create(true).append(true).open(path);
         }
-        let _ = editor.load_history(path.as_path())?;
+        editor.load_history(path)?;
     }
 
-    let mut vm = Vm::new();
+    vm.set_option("monitor", true);
+
+    let exit_status = loop {
+        signals::clear_interrupt();
+        for line in vm.job_notifications() {
+            eprintln!("{}", line);
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.update(vm);
+        }
 
-    loop {
         let cwd = env::current_dir()?;
         let line = editor.readline(format!("{} >> ", cwd.display()).as_str());
         match line {
             Ok(line) => {
                 let _ = editor.add_history_entry(line.as_str());
 
-                let scanner = Scanner::new(line);
-                let chunk = ByteCodeChunk::new();
-                let mut compiler = Compiler::new(scanner, chunk);
+                // Each line is saved as soon as it's entered, so that none are
+                // lost if the shell is killed.
+                #[cfg(feature = "with-file-history")]
+                if let Some(path) = &history {
+                    let _ = editor.append_history(path);
+                }
+
+                let mut compiler = vm.compiler(line.clone());
 
                 match compiler.compile_to(TokenType::EndOfFile) {
                     Err(e) => {
-                        println!("error: {:?}", e)
+                        let location = compiler.error_location(&e);
+                        eprint!("{}", diagnostic::render(STDIN, &line, &location, &e));
                     }
                     Ok(_) => {
                         let chunk = compiler.into_chunk();
                         match vm.run(chunk, EvaluateContext::None) {
-                            Err(e) => {
-                                println!("runtime error: {:?}", e)
-                            }
+                            Err(VmError::Exit(status)) => break status,
+                            Err(VmError::Interrupted) => println!(),
+                            Err(e) => report_runtime_error(vm, STDIN, &line, &e),
                             Ok(value) => {
                                 println!("{}", value);
                             }
@@ -59,18 +102,21 @@ pub fn interactive() -> Result<()> {
                     }
                 };
             }
-            Err(ReadlineError::Interrupted) => break,
-            Err(ReadlineError::Eof) => {}
+            // Ctrl-C abandons the line being typed.
+            Err(ReadlineError::Interrupted) => {}
+            Err(ReadlineError::Eof) => {
+                if vm.options().ignoreeof && io::stdin().is_terminal() {
+                    eprintln!("Use \"exit\" to leave the shell.");
+                    continue;
+                }
+                eprintln!("exit");
+                break vm.last_status();
+            }
             Err(err) => {
                 println!("Error: {:?}", err);
             }
         }
-    }
-
-    #[cfg(feature = "with-file-history")]
-    if let Some(dir) = dirs::preference_dir() {
-        editor.save_history(dir.as_path().join("insh/history").as_path())?;
-    }
+    };
 
-    Ok(())
+    Ok(vm.run_exit_trap(exit_status))
 }
@@ -1,34 +1,74 @@
+mod cli;
 mod compile;
+mod diagnostic;
+mod editor;
 mod interactive;
 mod scan;
 mod script;
 mod vm;
 
-use std::env;
+use std::{
+    env,
+    io::{self, Write},
+    process,
+};
 
+use cli::{Command, Source};
 use interactive::interactive;
 use rustyline::Result;
-use script::run_script;
+use script::{run_script, run_source, run_startup_file, run_stdin};
 use vm::vm::Vm;
 
 fn main() -> Result<()> {
-    println!("insh v0.5.0");
+    let mut args = env::args();
+    let program = args.next().unwrap_or_default();
 
-    let args: Vec<String> = env::args().skip(1).collect();
-    let mut scripts = Vec::new();
+    let arguments = match cli::parse(&program, args) {
+        Command::Run(arguments) => arguments,
+        Command::Show(text) => {
+            // e.g. `insh --help | head` closes the pipe before it's all read.
+            let _ = writeln!(io::stdout(), "{}", text);
+            process::exit(0);
+        }
+        Command::Fail(message) => {
+            eprintln!("insh: {}", message);
+            eprintln!("Try `insh --help` for more information.");
+            process::exit(2);
+        }
+    };
 
-    for arg in args {
-        scripts.push(arg);
+    let mut vm = Vm::new();
+    for option in &arguments.options {
+        vm.set_option(option, true);
     }
+    vm.set_args(arguments.name, arguments.args);
 
-    if scripts.is_empty() {
-        interactive()?;
-    } else {
-        let mut vm = Vm::new();
-        for script in scripts {
-            run_script(&mut vm, script)?;
-        }
+    let interactive_shell = matches!(arguments.source, Source::Interactive);
+    vm::signals::install(interactive_shell);
+
+    // Startup files aren't run when only checking syntax, as nothing is.
+    let check_only = arguments.check_only && !interactive_shell;
+    if arguments.login && !check_only {
+        run_startup_file(&mut vm, ".insh_profile");
     }
 
-    Ok(())
+    let status = match arguments.source {
+        Source::Interactive => {
+            if !arguments.norc {
+                run_startup_file(&mut vm, ".inshrc");
+            }
+            println!("insh v{}", env!("CARGO_PKG_VERSION"));
+            interactive(&mut vm)?
+        }
+        Source::Script(name) => run_script(&mut vm, &name, check_only),
+        Source::Code(code) => run_source(&mut vm, "-c", code, check_only),
+        Source::Stdin => run_stdin(&mut vm, check_only),
+    };
+
+    let status = if check_only {
+        status
+    } else {
+        vm.run_exit_trap(status)
+    };
+    process::exit(status)
 }
@@ -1,7 +1,37 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::diagnostic::Diagnostic;
+
 #[derive(Debug)]
 pub enum ScanError {
-    UnrecognisedCharacter,
+    UnrecognisedCharacter(char),
     UnknownVariableType,
     MissingStringDelimiter,
     InvalidNumber,
 }
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::UnrecognisedCharacter(c) => write!(f, "unrecognised character `{}`", c),
+            ScanError::UnknownVariableType => write!(f, "unknown kind of variable"),
+            ScanError::MissingStringDelimiter => write!(f, "unterminated string"),
+            ScanError::InvalidNumber => write!(f, "invalid number"),
+        }
+    }
+}
+
+impl Diagnostic for ScanError {
+    fn hint(&self) -> Option<String> {
+        match self {
+            ScanError::UnknownVariableType => {
+                Some("variables start with `@` (locals) or `$` (environment)".to_owned())
+            }
+            ScanError::MissingStringDelimiter => Some("add a closing `\"`".to_owned()),
+            ScanError::InvalidNumber => {
+                Some("a number has at most one `.`, or three for an IPv4 address".to_owned())
+            }
+            _ => None,
+        }
+    }
+}
@@ -10,15 +10,34 @@ pub enum ScannerMode {
     Argument,
 }
 
+/// The words the scanner reads as keywords rather than identifiers.
+pub const KEYWORDS: [&str; 22] = [
+    "and", "break", "continue", "do", "else", "false", "for", "from", "if", "in", "is", "len",
+    "let", "not", "or", "pin", "pinned", "read", "then", "true", "until", "while",
+];
+
 pub struct Scanner {
     start_offset: usize,
     current_offset: usize,
     current_column: i64,
     current_line: i64,
+    start_column: i64,
+    start_line: i64,
     chars: Vec<char>,
     mode: ScannerMode,
     mode_stack: Vec<ScannerMode>,
 
+    // Set between `for` and its `in`, which only then starts a list of items.
+    for_header: bool,
+    // Set while reading the items of a `for` loop, where `do` ends the list
+    // rather than being an argument.
+    for_items: bool,
+    // Set after a `.`, where the next word is a field name.
+    field_name: bool,
+    // The number of `do` loops whose closing `while` hasn't been read yet.
+    // Outside of these, `while` is an ordinary argument.
+    do_bodies: usize,
+
     debug_output_tokens: bool,
 }
 
@@ -29,14 +48,30 @@ impl Scanner {
             current_offset: 0,
             current_column: 0,
             current_line: 1,
+            start_column: 0,
+            start_line: 1,
             chars: text.chars().collect(),
             mode: ScannerMode::Command,
             mode_stack: Vec::new(),
+            for_header: false,
+            for_items: false,
+            field_name: false,
+            do_bodies: 0,
 
-            debug_output_tokens: true,
+            debug_output_tokens: false,
         }
     }
 
+    /// Turns printing of each token as it's read on or off.
+    pub fn set_debug_output(&mut self, on: bool) {
+        self.debug_output_tokens = on;
+    }
+
+    /// How the next word will be read, e.g. as a command or an argument.
+    pub fn mode(&self) -> ScannerMode {
+        self.mode
+    }
+
     fn push_mode(&mut self, mode: ScannerMode) {
         // Don't push "Command" mode to stack, as we never
         // want to return to it.
@@ -59,6 +94,11 @@ impl Scanner {
         while !self.is_at_end() && (self.current_char().is_digit(10) || self.current_char() == '.')
         {
             if self.current_char() == '.' {
+                // `..` is a range, not part of the number.
+                if self.peek_char() == '.' {
+                    break;
+                }
+
                 dots += 1;
             }
             self.next_char();
@@ -76,9 +116,6 @@ impl Scanner {
 
     fn read_string(&mut self) -> Result<Token, ScanError> {
         while !self.is_at_end() && self.current_char() != '"' {
-            if self.current_char() == '\n' {
-                self.current_line += 1;
-            }
             self.next_char();
         }
 
@@ -93,6 +130,8 @@ impl Scanner {
     fn identifier_type(&self) -> TokenType {
         match self.chars[self.start_offset] {
             'a' => self.check_keyword(1, "nd", TokenType::And),
+            'b' => self.check_keyword(1, "reak", TokenType::Break),
+            'c' => self.check_keyword(1, "ontinue", TokenType::Continue),
             'd' => self.check_keyword(1, "o", TokenType::Do),
             'e' => self.check_keyword(1, "lse", TokenType::Else),
             'f' => {
@@ -110,15 +149,20 @@ impl Scanner {
             'i' => {
                 if self.current_offset - self.start_offset > 1 {
                     match self.chars[self.start_offset + 1] {
-                        'f' => TokenType::If,
-                        's' => TokenType::Is,
+                        'f' => self.check_keyword(2, "", TokenType::If),
+                        'n' => self.check_keyword(2, "", TokenType::In),
+                        's' => self.check_keyword(2, "", TokenType::Is),
                         _ => TokenType::Identifier,
                     }
                 } else {
                     TokenType::Identifier
                 }
             }
-            'l' => self.check_keyword(1, "et", TokenType::Let),
+            'l' => match self.check_keyword(1, "et", TokenType::Let) {
+                TokenType::Identifier => self.check_keyword(1, "en", TokenType::Len),
+                other => other,
+            },
+            'n' => self.check_keyword(1, "ot", TokenType::Not),
             'o' => self.check_keyword(1, "r", TokenType::Or),
             'p' => {
                 if self.current_offset - self.start_offset > 1 {
@@ -176,18 +220,48 @@ impl Scanner {
             TokenType::Do | TokenType::If | TokenType::Then | TokenType::Else
         ) {
             self.push_mode(ScannerMode::Command);
+        } else if token_type == TokenType::While {
+            // The end of a `do` body can leave an expression unfinished, as in
+            // `do { @i = @i + 1 } while @i < 3`.
+            self.do_bodies = self.do_bodies.saturating_sub(1);
+        } else if token_type == TokenType::For {
+            self.for_header = true;
+        } else if token_type == TokenType::In && self.for_header {
+            // The items of a `for` loop are read like the arguments of a
+            // command, so that e.g. globs can be written unquoted.
+            self.push_mode(ScannerMode::Argument);
+            self.for_header = false;
+            self.for_items = true;
         }
 
         Ok(self.new_token(token_type, None, None))
     }
 
     fn read_command(&mut self) -> Result<Token, ScanError> {
-        while !self.is_at_end() && !self.current_char().is_whitespace() {
+        while !self.is_at_end()
+            && !self.current_char().is_whitespace()
+            && !matches!(self.current_char(), ';' | ')' | '}' | '|')
+        {
             self.next_char();
         }
 
         let (mode, token_type) = match self.identifier_type() {
             TokenType::Identifier => (ScannerMode::Argument, TokenType::Command),
+            // These are followed by a statement, e.g. `do echo hi while ...`
+            other @ (TokenType::Do | TokenType::Then | TokenType::Else) => {
+                if other == TokenType::Do {
+                    self.do_bodies += 1;
+                }
+                (ScannerMode::Command, other)
+            }
+            TokenType::While => {
+                self.do_bodies = self.do_bodies.saturating_sub(1);
+                (ScannerMode::Expression, TokenType::While)
+            }
+            TokenType::For => {
+                self.for_header = true;
+                (ScannerMode::Expression, TokenType::For)
+            }
             other => (ScannerMode::Expression, other),
         };
 
@@ -199,20 +273,42 @@ impl Scanner {
         while !self.is_at_end()
             && !self.current_char().is_whitespace()
             && self.current_char() != ')'
+            && self.current_char() != ';'
+            && self.current_char() != '>'
+            && self.current_char() != '<'
+            && self.current_char() != '&'
+            && !self.at_range_bound()
         {
             self.next_char();
         }
 
         let token_type = match self.identifier_type() {
+            TokenType::Do if self.for_items => {
+                self.for_items = false;
+                TokenType::Do
+            }
+            TokenType::While if self.do_bodies > 0 => {
+                self.do_bodies -= 1;
+                TokenType::While
+            }
+            TokenType::Identifier if self.is_glob() => TokenType::Glob,
+            TokenType::Identifier if self.is_range() => TokenType::Range,
             TokenType::Identifier => TokenType::String,
             TokenType::Is
+            | TokenType::In
             | TokenType::And
             | TokenType::Or
+            | TokenType::Not
+            | TokenType::Len
             | TokenType::For
             | TokenType::From
             | TokenType::Do
             | TokenType::If
-            | TokenType::Else => TokenType::String,
+            | TokenType::Else
+            | TokenType::While
+            | TokenType::Until
+            | TokenType::Break
+            | TokenType::Continue => TokenType::String,
             other => other,
         };
 
@@ -221,11 +317,65 @@ impl Scanner {
             TokenType::Do | TokenType::Then | TokenType::Else
         ) {
             self.push_mode(ScannerMode::Command)
+        } else if token_type == TokenType::While {
+            // The condition ending a `do ... while` loop.
+            self.push_mode(ScannerMode::Expression)
         }
 
         Ok(self.new_token(token_type, None, None))
     }
 
+    fn read_field_name(&mut self) -> Result<Token, ScanError> {
+        while !self.is_at_end()
+            && (self.current_char().is_alphanumeric() || self.current_char() == '_')
+        {
+            self.next_char();
+        }
+
+        Ok(self.new_token(TokenType::Identifier, None, None))
+    }
+
+    /// Whether the current token is directly after a variable, index or
+    /// group, with no space between.
+    fn follows_value(&self) -> bool {
+        match self.start_offset.checked_sub(1).map(|i| self.chars[i]) {
+            Some(c) => c.is_alphanumeric() || matches!(c, '_' | ']' | ')'),
+            None => false,
+        }
+    }
+
+    fn is_glob(&self) -> bool {
+        self.chars[self.start_offset..self.current_offset]
+            .iter()
+            .any(|c| matches!(c, '*' | '?' | '['))
+    }
+
+    /// Whether the current word is an integer range, e.g. `1..10`.
+    fn is_range(&self) -> bool {
+        let word: String = self.chars[self.start_offset..self.current_offset]
+            .iter()
+            .collect();
+
+        match word.split_once("..") {
+            Some((from, to)) => from.parse::<i64>().is_ok() && to.parse::<i64>().is_ok(),
+            None => false,
+        }
+    }
+
+    /// Whether a `for` item is an integer followed by `..`, e.g. the `1` of
+    /// `1..@n`, so that the `..` starts a range rather than continuing the
+    /// word.
+    fn at_range_bound(&self) -> bool {
+        if !self.for_items || self.current_char() != '.' || self.peek_char() != '.' {
+            return false;
+        }
+
+        let word: String = self.chars[self.start_offset..self.current_offset]
+            .iter()
+            .collect();
+        word.parse::<i64>().is_ok()
+    }
+
     fn read_variable(&mut self) -> Result<Token, ScanError> {
         let token_type = match self.chars[self.current_offset - 1] {
             '$' => TokenType::EnvironmentVariable,
@@ -233,6 +383,10 @@ impl Scanner {
             _ => return Err(ScanError::UnknownVariableType),
         };
 
+        // A `@` or `$` on its own at the end is still being typed.
+        if self.is_at_end() {
+            return Ok(self.new_token(token_type, Some(1), Some(0)));
+        }
         self.next_char();
 
         while !self.is_at_end()
@@ -243,7 +397,15 @@ impl Scanner {
             self.next_char();
         }
 
-        Ok(self.new_token(token_type, Some(1), Some(0)))
+        let mut token = self.new_token(token_type, Some(1), Some(0));
+
+        // `$?` (or `@?`) is the exit status of the last command.
+        if token.text == "?" {
+            token.token_type = TokenType::LocalVariable;
+            token.text = "status".to_owned();
+        }
+
+        Ok(token)
     }
 
     fn is_at_end(&self) -> bool {
@@ -253,7 +415,14 @@ impl Scanner {
     fn next_char(&mut self) -> char {
         let c = self.chars[self.current_offset];
         self.current_offset += 1;
-        self.current_column += 1;
+
+        if c == '\n' {
+            self.current_line += 1;
+            self.current_column = 0;
+        } else {
+            self.current_column += 1;
+        }
+
         c
     }
 
@@ -265,6 +434,14 @@ impl Scanner {
         }
     }
 
+    fn peek_char(&self) -> char {
+        if self.current_offset + 1 >= self.chars.len() {
+            '\0'
+        } else {
+            self.chars[self.current_offset + 1]
+        }
+    }
+
     fn check_keyword(&self, from: isize, rest: &str, token_type: TokenType) -> TokenType {
         let len = rest.len();
         if self.current_offset.wrapping_sub(self.start_offset) == len.wrapping_add_signed(from) {
@@ -282,22 +459,36 @@ impl Scanner {
     }
 
     fn skip_whitespace(&mut self) {
-        while " \r\t#".contains(self.current_char()) {
-            if self.current_char() == '#' {
-                while !self.is_at_end() && self.current_char() != '\n' {
+        loop {
+            match self.current_char() {
+                ' ' | '\r' | '\t' => {
                     self.next_char();
                 }
+                // A comment runs up to, but not including, the end of the line.
+                '#' => {
+                    while !self.is_at_end() && self.current_char() != '\n' {
+                        self.next_char();
+                    }
+                }
+                _ => break,
             }
-            self.next_char();
         }
 
         if self.current_char() == '\n' {
-            self.current_line += 1;
-            self.current_column += 1;
             self.mode = ScannerMode::Command
         }
     }
 
+    /// The line and column (both from 1) of the token being read, and how
+    /// many characters of it have been read so far.
+    pub fn token_position(&self) -> (i64, i64, usize) {
+        (
+            self.start_line,
+            self.start_column + 1,
+            self.current_offset - self.start_offset,
+        )
+    }
+
     fn new_token(
         &self,
         token_type: TokenType,
@@ -312,17 +503,22 @@ impl Scanner {
             .collect();
 
         if self.debug_output_tokens {
-            println!(
+            eprintln!(
                 "L{:04} C{:02} ({:04}) {:12} [{}]",
-                self.current_line,
-                self.current_column,
+                self.start_line,
+                self.start_column + 1,
                 self.start_offset,
                 format!("[{:02}] {}", token_type as u8, token_type),
                 raw
             );
         }
 
-        Token::new(token_type, raw)
+        let mut token = Token::new(token_type, raw);
+        token.start = self.start_offset as i64;
+        token.end = self.current_offset as i64;
+        token.line = self.start_line;
+        token.column = self.start_column + 1;
+        token
     }
 
     fn token_if_match(
@@ -339,16 +535,54 @@ impl Scanner {
         }
     }
 
+    /// Reads every token in `text` without printing them, e.g. to colour the
+    /// line being typed. Each token's `start` and `end` give the characters
+    /// it covers. Text that can't be read doesn't stop it: an unterminated
+    /// string is still a `String`, and anything else becomes `Unknown`.
+    pub fn tokenize(text: &str) -> Vec<Token> {
+        let mut scanner = Scanner::new(text.to_owned());
+        let mut tokens = Vec::new();
+        loop {
+            match scanner.read_token() {
+                Ok(token) if token.token_type == TokenType::EndOfFile => break,
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    if scanner.current_offset == scanner.start_offset {
+                        if scanner.is_at_end() {
+                            break;
+                        }
+                        scanner.next_char();
+                    }
+                    let token_type = match e {
+                        ScanError::MissingStringDelimiter => TokenType::String,
+                        _ => TokenType::Unknown,
+                    };
+                    tokens.push(scanner.new_token(token_type, None, None));
+                }
+            }
+        }
+        tokens
+    }
+
     pub fn read_token(&mut self) -> Result<Token, ScanError> {
         self.skip_whitespace();
 
         self.start_offset = self.current_offset;
+        self.start_line = self.current_line;
+        self.start_column = self.current_column;
 
         if self.is_at_end() {
             Ok(self.new_token(TokenType::EndOfFile, None, None))
         } else {
             let c = self.next_char();
 
+            if self.field_name {
+                self.field_name = false;
+                if c.is_alphanumeric() || c == '_' {
+                    return self.read_field_name();
+                }
+            }
+
             loop {
                 match (self.mode, c) {
                     (_, '\n') => {
@@ -358,8 +592,6 @@ impl Scanner {
                             TokenType::EndOfLine
                         };
 
-                        self.current_line += 1;
-                        self.current_column = 0;
                         self.push_mode(ScannerMode::Command);
 
                         return Ok(self.new_token(tt, None, None));
@@ -368,12 +600,22 @@ impl Scanner {
                     (_, '#') => while !self.is_at_end() && self.next_char() != '\n' {},
 
                     (_, ';') => {
+                        self.push_mode(ScannerMode::Command);
                         return Ok(self.new_token(TokenType::Semicolon, None, None));
                     }
 
-                    (ScannerMode::Command, '(' | '"' | '@' | '$' | '-') => {
+                    (ScannerMode::Command, '(' | '"' | '@' | '$' | '-' | '!') => {
+                        self.push_mode(ScannerMode::Expression)
+                    }
+                    // A `[` on its own is the `test` command.
+                    (ScannerMode::Command, '[')
+                        if !self.is_at_end() && !self.current_char().is_whitespace() =>
+                    {
                         self.push_mode(ScannerMode::Expression)
                     }
+                    (ScannerMode::Command, ':') if self.current_char() == '}' => {
+                        return Ok(self.new_token(TokenType::Colon, None, None));
+                    }
                     (ScannerMode::Command, '{') => {
                         self.push_mode(ScannerMode::Command);
                         return Ok(self.new_token(TokenType::OpenBrace, None, None));
@@ -382,6 +624,10 @@ impl Scanner {
                         self.pop_mode();
                         return Ok(self.new_token(TokenType::CloseBrace, None, None));
                     }
+                    (ScannerMode::Command, ')') => {
+                        self.pop_mode();
+                        return Ok(self.new_token(TokenType::CloseBracket, None, None));
+                    }
                     (ScannerMode::Command, _) => {
                         if c.is_numeric() {
                             self.mode = ScannerMode::Expression;
@@ -390,9 +636,38 @@ impl Scanner {
                         }
                     }
 
-                    (ScannerMode::Argument, '(' | '|' | '=') => {
-                        self.push_mode(ScannerMode::Expression)
+                    (ScannerMode::Argument, '(') => {
+                        self.push_mode(ScannerMode::Command);
+                        return Ok(self.new_token(TokenType::OpenBracket, None, None));
+                    }
+                    // Indexing, e.g. `@xs[0]`, or a list as a `for` item,
+                    // e.g. `for @x in [1, 2]`. Otherwise a `[` that starts a
+                    // word is part of a glob.
+                    (ScannerMode::Argument, '[')
+                        if self.for_items
+                            || (self.start_offset > 0
+                                && !self.chars[self.start_offset - 1].is_whitespace()) =>
+                    {
+                        self.push_mode(ScannerMode::Expression);
+                        return Ok(self.new_token(TokenType::OpenSquare, None, None));
                     }
+                    // A range between two `for` items, e.g. `1..@n`.
+                    (ScannerMode::Argument, '.')
+                        if self.for_items && self.current_char() == '.' && self.follows_value() =>
+                    {
+                        self.next_char();
+                        return Ok(self.new_token(TokenType::DotDot, None, None));
+                    }
+                    // Field access, e.g. `@user.name`.
+                    (ScannerMode::Argument, '.')
+                        if self.follows_value()
+                            && (self.current_char().is_alphabetic()
+                                || self.current_char() == '_') =>
+                    {
+                        self.field_name = true;
+                        return Ok(self.new_token(TokenType::Dot, None, None));
+                    }
+                    (ScannerMode::Argument, '|' | '=') => self.push_mode(ScannerMode::Expression),
                     (ScannerMode::Argument, '"') => return self.read_string(),
                     (ScannerMode::Argument, '@' | '$') => return self.read_variable(),
                     (ScannerMode::Argument, ')') => {
@@ -403,6 +678,37 @@ impl Scanner {
                         self.pop_mode();
                         return Ok(self.new_token(TokenType::CloseBrace, None, None));
                     }
+                    (ScannerMode::Argument, '>') => {
+                        let ttype = self.token_if_match(
+                            '>',
+                            TokenType::RedirectAppend,
+                            TokenType::RedirectOutput,
+                        );
+                        return Ok(self.new_token(ttype, None, None));
+                    }
+                    (ScannerMode::Argument, '<') => {
+                        return Ok(self.new_token(TokenType::RedirectInput, None, None));
+                    }
+                    (ScannerMode::Argument, '2') if self.current_char() == '>' => {
+                        self.next_char();
+                        let ttype = if self.current_char() == '&' && self.peek_char() == '1' {
+                            self.next_char();
+                            self.next_char();
+                            TokenType::RedirectErrorToOutput
+                        } else {
+                            TokenType::RedirectError
+                        };
+                        return Ok(self.new_token(ttype, None, None));
+                    }
+                    (ScannerMode::Argument, '&') if self.current_char() == '>' => {
+                        self.next_char();
+                        return Ok(self.new_token(TokenType::RedirectAll, None, None));
+                    }
+                    (ScannerMode::Argument | ScannerMode::Expression, '&') => {
+                        // Like `;`, a `&` can be followed by another command.
+                        self.push_mode(ScannerMode::Command);
+                        return Ok(self.new_token(TokenType::Ampersand, None, None));
+                    }
                     (ScannerMode::Argument, _) => return self.read_argument(),
 
                     (ScannerMode::Expression, '(') => {
@@ -421,6 +727,19 @@ impl Scanner {
                         self.pop_mode();
                         return Ok(self.new_token(TokenType::CloseBrace, None, None));
                     }
+                    (ScannerMode::Expression, '[') => {
+                        self.push_mode(ScannerMode::Expression);
+                        return Ok(self.new_token(TokenType::OpenSquare, None, None));
+                    }
+                    (ScannerMode::Expression, ']') => {
+                        self.pop_mode();
+                        return Ok(self.new_token(TokenType::CloseSquare, None, None));
+                    }
+                    (ScannerMode::Expression, '.') => {
+                        let ttype = self.token_if_match('.', TokenType::DotDot, TokenType::Dot);
+                        self.field_name = ttype == TokenType::Dot;
+                        return Ok(self.new_token(ttype, None, None));
+                    }
                     (ScannerMode::Expression, ',') => {
                         return Ok(self.new_token(TokenType::Comma, None, None))
                     }
@@ -445,7 +764,6 @@ impl Scanner {
                         return Ok(self.new_token(ttype, None, None));
                     }
                     (ScannerMode::Expression, ':') => {
-                        self.push_mode(ScannerMode::Command);
                         return Ok(self.new_token(TokenType::Colon, None, None));
                     }
                     (ScannerMode::Expression, '!') => {
@@ -495,7 +813,7 @@ impl Scanner {
                         } else if c.is_alphabetic() {
                             self.read_identifier()
                         } else {
-                            Err(ScanError::UnrecognisedCharacter)
+                            Err(ScanError::UnrecognisedCharacter(c))
                         }
                     }
                 }
@@ -510,11 +828,17 @@ impl Default for Scanner {
             start_offset: 0,
             current_offset: 0,
             current_column: 0,
-            current_line: 0,
+            current_line: 1,
+            start_column: 0,
+            start_line: 1,
             chars: Vec::new(),
             mode: ScannerMode::Command,
             mode_stack: Vec::new(),
-            debug_output_tokens: true,
+            for_header: false,
+            for_items: false,
+            field_name: false,
+            do_bodies: 0,
+            debug_output_tokens: false,
         }
     }
 }
@@ -12,6 +12,7 @@ pub enum TokenType {
     EnvironmentVariable,
 
     Dot,
+    DotDot,
     Minus,
     Plus,
     Slash,
@@ -20,6 +21,8 @@ pub enum TokenType {
     CloseBracket,
     OpenBrace,
     CloseBrace,
+    OpenSquare,
+    CloseSquare,
     Comma,
 
     Bang,
@@ -39,6 +42,14 @@ pub enum TokenType {
     Colon,
     Semicolon,
     Pipe,
+    Ampersand,
+
+    RedirectOutput,
+    RedirectAppend,
+    RedirectInput,
+    RedirectError,
+    RedirectErrorToOutput,
+    RedirectAll,
 
     Is,
     Pinned,
@@ -53,6 +64,8 @@ pub enum TokenType {
     Int,
     IPv4,
     Path,
+    Glob,
+    Range,
     False,
     True,
     EndCommand,
@@ -64,9 +77,14 @@ pub enum TokenType {
     While,
     Until,
     Do,
+    Break,
+    Continue,
     And,
     Or,
+    Not,
+    Len,
     For,
+    In,
     Read,
     From,
 }
@@ -77,6 +95,8 @@ pub struct Token {
     pub raw: Vec<char>,
     pub start: i64,
     pub end: i64,
+    pub line: i64,
+    pub column: i64,
     pub text: String,
 }
 
@@ -88,6 +108,8 @@ impl Token {
             text,
             start: 0,
             end: 0,
+            line: 0,
+            column: 0,
         }
     }
 
@@ -96,6 +118,87 @@ impl Token {
     }
 }
 
+impl TokenType {
+    /// How the token is described to the user, e.g. in "expected `)`".
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TokenType::Unknown => "unknown token",
+            TokenType::EndOfFile => "end of input",
+            TokenType::EndOfLine => "end of line",
+            TokenType::EndCommand => "end of command",
+            TokenType::Let => "`let`",
+            TokenType::Pin => "`pin`",
+            TokenType::Pinned => "`pinned`",
+            TokenType::LocalVariable => "a local variable",
+            TokenType::EnvironmentVariable => "an environment variable",
+            TokenType::Dot => "`.`",
+            TokenType::DotDot => "`..`",
+            TokenType::Minus => "`-`",
+            TokenType::Plus => "`+`",
+            TokenType::Slash => "`/`",
+            TokenType::Star => "`*`",
+            TokenType::OpenBracket => "`(`",
+            TokenType::CloseBracket => "`)`",
+            TokenType::OpenBrace => "`{`",
+            TokenType::CloseBrace => "`}`",
+            TokenType::OpenSquare => "`[`",
+            TokenType::CloseSquare => "`]`",
+            TokenType::Comma => "`,`",
+            TokenType::Bang => "`!`",
+            TokenType::BangEqual => "`!=`",
+            TokenType::Equal => "`=`",
+            TokenType::QuestionEqual => "`?=`",
+            TokenType::EqualEqual => "`==`",
+            TokenType::Greater => "`>`",
+            TokenType::GreaterEqual => "`>=`",
+            TokenType::Less => "`<`",
+            TokenType::LessEqual => "`<=`",
+            TokenType::EqualGreater => "`=>`",
+            TokenType::LessEqualGreater => "`<=>`",
+            TokenType::MinusGreater => "`->`",
+            TokenType::Question => "`?`",
+            TokenType::Colon => "`:`",
+            TokenType::Semicolon => "`;`",
+            TokenType::Pipe => "`|`",
+            TokenType::Ampersand => "`&`",
+            TokenType::RedirectOutput => "`>`",
+            TokenType::RedirectAppend => "`>>`",
+            TokenType::RedirectInput => "`<`",
+            TokenType::RedirectError => "`2>`",
+            TokenType::RedirectErrorToOutput => "`2>&1`",
+            TokenType::RedirectAll => "`&>`",
+            TokenType::Is => "`is`",
+            TokenType::Command => "a command",
+            TokenType::Identifier => "a name",
+            TokenType::String => "a string",
+            TokenType::Float => "a number",
+            TokenType::Int => "a number",
+            TokenType::IPv4 => "an IPv4 address",
+            TokenType::Path => "a path",
+            TokenType::Glob => "a pattern",
+            TokenType::Range => "a range",
+            TokenType::False => "`false`",
+            TokenType::True => "`true`",
+            TokenType::If => "`if`",
+            TokenType::Then => "`then`",
+            TokenType::Else => "`else`",
+            TokenType::While => "`while`",
+            TokenType::Until => "`until`",
+            TokenType::Do => "`do`",
+            TokenType::Break => "`break`",
+            TokenType::Continue => "`continue`",
+            TokenType::And => "`and`",
+            TokenType::Or => "`or`",
+            TokenType::Not => "`not`",
+            TokenType::Len => "`len`",
+            TokenType::For => "`for`",
+            TokenType::In => "`in`",
+            TokenType::Read => "`read`",
+            TokenType::From => "`from`",
+        }
+    }
+}
+
 impl Display for TokenType {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
@@ -106,16 +209,21 @@ impl Display for TokenType {
                 TokenType::Command => "Command",
                 TokenType::EndOfFile => "EndOfFile",
                 TokenType::Dot => "Dot",
+                TokenType::DotDot => "DotDot",
                 TokenType::Minus => "Minus",
                 TokenType::Plus => "Plus",
                 TokenType::Slash => "Slash",
                 TokenType::Star => "Star",
                 TokenType::And => "And",
                 TokenType::Or => "Or",
+                TokenType::Not => "Not",
+                TokenType::Len => "Len",
                 TokenType::OpenBracket => "OpenBracket",
                 TokenType::CloseBracket => "CloseBracket",
                 TokenType::OpenBrace => "OpenBrace",
                 TokenType::CloseBrace => "CloseBrace",
+                TokenType::OpenSquare => "OpenSquare",
+                TokenType::CloseSquare => "CloseSquare",
                 TokenType::Comma => "Comma",
                 TokenType::Bang => "Bang",
                 TokenType::BangEqual => "BangEqual",
@@ -137,16 +245,28 @@ impl Display for TokenType {
                 TokenType::LocalVariable => "Local",
                 TokenType::EnvironmentVariable => "Env",
                 TokenType::Path => "Path",
+                TokenType::Glob => "Glob",
+                TokenType::Range => "Range",
                 TokenType::Question => "Question",
                 TokenType::Colon => "Colon",
                 TokenType::Pipe => "Pipe",
+                TokenType::Ampersand => "Ampersand",
+                TokenType::RedirectOutput => "RedirectOutput",
+                TokenType::RedirectAppend => "RedirectAppend",
+                TokenType::RedirectInput => "RedirectInput",
+                TokenType::RedirectError => "RedirectError",
+                TokenType::RedirectErrorToOutput => "RedirectErrorToOutput",
+                TokenType::RedirectAll => "RedirectAll",
                 TokenType::EqualGreater => "EqualGreater",
                 TokenType::LessEqualGreater => "LessEqualGreater",
                 TokenType::While => "While",
                 TokenType::Until => "Until",
                 TokenType::Do => "Do",
+                TokenType::Break => "Break",
+                TokenType::Continue => "Continue",
                 TokenType::Is => "Is",
                 TokenType::For => "For",
+                TokenType::In => "In",
                 TokenType::True => "True",
                 TokenType::False => "False",
                 TokenType::Read => "Read",
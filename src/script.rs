@@ -1,40 +1,97 @@
 use std::{
-    fs::File,
-    io::{BufReader, Read},
+    fs,
+    io::{self, ErrorKind, Read},
 };
 
-use rustyline::Result;
-
 use crate::{
-    compile::compiler::Compiler,
-    scan::{scanner::Scanner, tokens::TokenType},
-    vm::{chunk::bytecode_chunk::ByteCodeChunk, evaluate::EvaluateContext, vm::Vm},
+    diagnostic::{self, Location},
+    scan::tokens::TokenType,
+    vm::{
+        builtins::describe,
+        evaluate::EvaluateContext,
+        value::ivalue,
+        vm::{Vm, VmError},
+    },
 };
 
-pub fn run_script(vm: &mut Vm, name: String) -> Result<()> {
-    let file = File::open(name)?;
-    let mut reader = BufReader::new(file);
+/// The name errors in code read from standard input are reported against.
+pub const STDIN: &str = "<stdin>";
 
-    let mut buffer = String::new();
-    reader.read_to_string(&mut buffer)?;
+/// Prints a runtime error, with the source it came from if it's known.
+pub fn report_runtime_error(vm: &Vm, name: &str, source: &str, error: &VmError) {
+    match vm.error_position() {
+        Some((line, column)) => {
+            let location = Location {
+                line,
+                column,
+                length: 1,
+            };
+            eprint!("{}", diagnostic::render(name, source, &location, error));
+        }
+        None => eprint!("{}", diagnostic::render_plain(error)),
+    }
+}
 
-    let scanner = Scanner::new(buffer);
-    let mut compiler = Compiler::new(scanner, ByteCodeChunk::new());
+/// Runs a script file, returning its exit status: the status of its last
+/// command, the code given to `exit`, or non-zero if it couldn't be read,
+/// compiled or run.
+pub fn run_script(vm: &mut Vm, name: &str, check_only: bool) -> ivalue {
+    match fs::read_to_string(name) {
+        Ok(source) => run_source(vm, name, source, check_only),
+        Err(e) => {
+            eprintln!("insh: {}: {}", name, describe(&e));
+            if e.kind() == ErrorKind::NotFound {
+                127
+            } else {
+                126
+            }
+        }
+    }
+}
+
+/// Runs a script read from standard input.
+pub fn run_stdin(vm: &mut Vm, check_only: bool) -> ivalue {
+    let mut source = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut source) {
+        eprintln!("insh: {}: {}", STDIN, describe(&e));
+        return 1;
+    }
+    run_source(vm, STDIN, source, check_only)
+}
+
+/// Runs `source` as a script called `name`, or with `check_only` just
+/// compiles it to report any errors.
+pub fn run_source(vm: &mut Vm, name: &str, source: String, check_only: bool) -> ivalue {
+    let mut compiler = vm.compiler(source.clone());
 
     match compiler.compile_to(TokenType::EndOfFile) {
         Err(e) => {
-            println!("compile error: {:?}", e)
+            let location = compiler.error_location(&e);
+            eprint!("{}", diagnostic::render(name, &source, &location, &e));
+            2
         }
+        Ok(_) if check_only => 0,
         Ok(_) => {
             let chunk = compiler.into_chunk();
             match vm.run(chunk, EvaluateContext::None) {
+                Err(VmError::Exit(status)) => status,
+                Err(e @ VmError::Interrupted) => e.exit_status(),
                 Err(e) => {
-                    println!("runtime error: {:?}", e)
+                    report_runtime_error(vm, name, &source, &e);
+                    e.exit_status()
                 }
-                Ok(_) => {}
-            };
+                Ok(_) => vm.last_status(),
+            }
         }
-    };
+    }
+}
 
-    Ok(())
+/// Runs a startup file in the home directory, e.g. `.inshrc`, if there is
+/// one.
+pub fn run_startup_file(vm: &mut Vm, file: &str) {
+    if let Some(path) = dirs::home_dir().map(|home| home.join(file)) {
+        if path.is_file() {
+            run_script(vm, &path.to_string_lossy(), false);
+        }
+    }
 }
@@ -1,8 +1,20 @@
+pub mod args;
+pub mod builtins;
 pub mod chunk;
+pub mod compare;
 pub mod evaluate;
+pub mod glob;
+pub mod iterate;
+pub mod jobs;
+pub mod list;
 pub mod local;
+pub mod map;
 pub mod op;
+pub mod options;
+pub mod redirect;
 pub mod runtime;
+pub mod signals;
 pub mod syscall;
+pub mod trace;
 pub mod value;
 pub mod vm;
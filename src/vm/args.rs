@@ -0,0 +1,42 @@
+use super::{
+    value::{ivalue, Value},
+    vm::Vm,
+};
+
+impl Vm {
+    /// Sets the name the shell or script was run as, `@0`, and the arguments
+    /// it was given.
+    pub fn set_args(&mut self, name: String, args: Vec<String>) {
+        self.define_global("0".to_owned(), Value::String(name));
+        self.set_positional(0, args);
+    }
+
+    /// The arguments in the innermost scope that has any: those of the
+    /// function invoked as a command, or else of the script.
+    pub(super) fn positional(&self) -> Option<(usize, Vec<String>)> {
+        let depth = self.scope_defining("args")?;
+        let args = match &self.scopes[depth].get_local(&"args".to_owned())?.value {
+            Value::List(items) => items.iter().map(|item| item.to_native_string()).collect(),
+            _ => Vec::new(),
+        };
+        Some((depth, args))
+    }
+
+    /// Defines the arguments in the scope at `depth`: as a list in `@args`,
+    /// one by one in `@1`, `@2`..., and their number in `@argc`.
+    pub(super) fn set_positional(&mut self, depth: usize, args: Vec<String>) {
+        // Any left from before that aren't replaced, e.g. after `shift`.
+        let mut n = args.len() + 1;
+        while self.remove_from_scope(depth, &n.to_string()).is_some() {
+            n += 1;
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            self.define_in_scope(depth, (i + 1).to_string(), Value::String(arg.clone()));
+        }
+        self.define_in_scope(depth, "argc".to_owned(), Value::Int(args.len() as ivalue));
+
+        let args = args.into_iter().map(Value::String).collect();
+        self.define_in_scope(depth, "args".to_owned(), Value::List(args));
+    }
+}
@@ -0,0 +1,94 @@
+pub mod complete;
+pub mod dirs;
+pub mod jobs;
+pub mod shell;
+pub mod trap;
+
+use std::{io, rc::Rc};
+
+use super::{
+    value::{ivalue, Value},
+    vm::Vm,
+};
+
+/// A command run inside the shell rather than as a separate process. It's
+/// given its arguments and returns its output along with an exit status.
+///
+/// When a builtin is run as a statement its output is printed, and when it's
+/// assigned (e.g. `let @x = (pwd)`) the output value itself is used, so a
+/// builtin can return a list or map as well as text.
+pub trait Builtin {
+    fn run(&self, vm: &mut Vm, args: Vec<Value>) -> (Value, ivalue);
+}
+
+impl<F> Builtin for F
+where
+    F: Fn(&mut Vm, Vec<Value>) -> (Value, ivalue),
+{
+    fn run(&self, vm: &mut Vm, args: Vec<Value>) -> (Value, ivalue) {
+        self(vm, args)
+    }
+}
+
+impl Vm {
+    /// Adds a builtin, replacing any existing one with the same name.
+    pub fn register(&mut self, name: &str, builtin: impl Builtin + 'static) {
+        self.builtins.insert(name.to_owned(), Rc::new(builtin));
+    }
+
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    pub fn builtin_names(&self) -> Vec<String> {
+        self.builtins.keys().cloned().collect()
+    }
+
+    /// The builtin to run for `cmd`, or `None` if it should be run as an
+    /// external command.
+    pub(super) fn builtin(&self, cmd: &str) -> Option<Rc<dyn Builtin>> {
+        self.builtins.get(cmd).cloned()
+    }
+
+    pub(super) fn register_core_builtins(&mut self) {
+        self.register("bg", Vm::builtin_bg);
+        self.register("cd", Vm::builtin_cd);
+        self.register("complete", Vm::builtin_complete);
+        self.register("dirs", Vm::builtin_dirs);
+        self.register("echo", Vm::builtin_echo);
+        self.register("exit", Vm::builtin_exit);
+        self.register("export", Vm::builtin_export);
+        self.register("fg", Vm::builtin_fg);
+        self.register("jobs", Vm::builtin_jobs);
+        self.register("kill", Vm::builtin_kill);
+        self.register("popd", Vm::builtin_popd);
+        self.register("pushd", Vm::builtin_pushd);
+        self.register("pwd", Vm::builtin_pwd);
+        self.register("set", Vm::builtin_set);
+        self.register("shift", Vm::builtin_shift);
+        self.register("source", Vm::builtin_source);
+        self.register("trap", Vm::builtin_trap);
+        self.register("type", Vm::builtin_type);
+        self.register("wait", Vm::builtin_wait);
+    }
+}
+
+/// The message for an error, without the OS error number that `io::Error`
+/// adds to it.
+pub fn describe(e: &io::Error) -> String {
+    let message = e.to_string();
+    match message.split_once(" (os error") {
+        Some((message, _)) => message.to_owned(),
+        None => message,
+    }
+}
+
+/// The result of a builtin that failed, having reported why.
+pub(super) fn failed(status: ivalue) -> (Value, ivalue) {
+    (Value::None, status)
+}
+
+/// The result of a builtin that succeeded, with the given text as output.
+pub(super) fn output(text: String) -> (Value, ivalue) {
+    (Value::String(text), 0)
+}
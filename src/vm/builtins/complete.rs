@@ -0,0 +1,91 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::vm::{
+    runtime,
+    value::{ivalue, Value},
+    vm::Vm,
+};
+
+use super::{failed, output};
+
+/// What Tab offers for the arguments of a command, as set by `complete`.
+#[derive(Clone, Debug)]
+pub enum CompletionSpec {
+    /// A fixed list of words, e.g. the subcommands of `git`.
+    Words(Vec<String>),
+    Files,
+    Directories,
+    Commands,
+}
+
+impl Display for CompletionSpec {
+    /// Shows the spec as the `complete` options that set it.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompletionSpec::Words(words) => write!(f, "-W '{}'", words.join(" ")),
+            CompletionSpec::Files => write!(f, "-f"),
+            CompletionSpec::Directories => write!(f, "-d"),
+            CompletionSpec::Commands => write!(f, "-c"),
+        }
+    }
+}
+
+impl Vm {
+    /// How to complete the arguments of each command that has a spec set.
+    pub fn completion_specs(&self) -> &HashMap<String, CompletionSpec> {
+        &self.completions
+    }
+
+    /// `complete (-W words | -f | -d | -c) names...` sets what Tab offers for
+    /// the arguments of the named commands: the given words, files,
+    /// directories or commands. `complete -r names...` removes the specs, and
+    /// with no arguments or `-p` they're listed.
+    pub(super) fn builtin_complete(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let mut args = runtime::arguments(&args).into_iter();
+
+        let spec = match args.next().as_deref() {
+            None | Some("-p") => {
+                let mut specs: Vec<(&String, &CompletionSpec)> = self.completions.iter().collect();
+                specs.sort_by_key(|(name, _)| *name);
+                let text: String = specs
+                    .iter()
+                    .map(|(name, spec)| format!("complete {} {}\n", spec, name))
+                    .collect();
+                return output(text);
+            }
+            Some("-r") => {
+                for name in args {
+                    self.completions.remove(&name);
+                }
+                return (Value::None, 0);
+            }
+            Some("-W") => match args.next() {
+                Some(words) => {
+                    CompletionSpec::Words(words.split_whitespace().map(str::to_owned).collect())
+                }
+                None => {
+                    eprintln!("complete: -W: expected a list of words");
+                    return failed(2);
+                }
+            },
+            Some("-f") => CompletionSpec::Files,
+            Some("-d") => CompletionSpec::Directories,
+            Some("-c") => CompletionSpec::Commands,
+            Some(arg) => {
+                eprintln!("complete: unexpected argument `{}`", arg);
+                return failed(2);
+            }
+        };
+
+        let names: Vec<String> = args.collect();
+        if names.is_empty() {
+            eprintln!("complete: expected a command name");
+            return failed(2);
+        }
+
+        for name in names {
+            self.completions.insert(name, spec.clone());
+        }
+        (Value::None, 0)
+    }
+}
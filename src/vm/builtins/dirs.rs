@@ -0,0 +1,176 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use crate::vm::{
+    value::{ivalue, Value},
+    vm::Vm,
+};
+
+use super::{describe, failed, output};
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), home()) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Shortens a path in the home directory to start with `~`, as `dirs` shows
+/// it.
+fn abbreviate(path: &Path) -> String {
+    match home().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Changes the shell's directory, keeping `$PWD` and `$OLDPWD` up to date.
+fn change_dir(path: &Path) -> io::Result<()> {
+    let old = env::current_dir()?;
+    env::set_current_dir(path)?;
+    env::set_var("OLDPWD", old);
+    env::set_var("PWD", env::current_dir()?);
+    Ok(())
+}
+
+impl Vm {
+    /// `cd [dir]` changes directory: to the home directory if none is given,
+    /// or back to the previous one for `cd -`.
+    pub(super) fn builtin_cd(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let (target, show) = match args.as_slice() {
+            [] => match home() {
+                Some(home) => (home, false),
+                None => {
+                    eprintln!("cd: HOME not set");
+                    return failed(1);
+                }
+            },
+            [arg] if arg.to_native_string() == "-" => match env::var_os("OLDPWD") {
+                Some(old) => (PathBuf::from(old), true),
+                None => {
+                    eprintln!("cd: OLDPWD not set");
+                    return failed(1);
+                }
+            },
+            [arg] => (expand_tilde(&arg.to_native_string()), false),
+            _ => {
+                eprintln!("cd: too many arguments");
+                return failed(1);
+            }
+        };
+
+        if let Err(e) = change_dir(&target) {
+            eprintln!("cd: {}: {}", target.display(), describe(&e));
+            return failed(1);
+        }
+
+        // Like other shells, `cd -` shows where it went.
+        if show {
+            output(format!("{}\n", target.display()))
+        } else {
+            (Value::None, 0)
+        }
+    }
+
+    pub(super) fn builtin_pwd(&mut self, _: Vec<Value>) -> (Value, ivalue) {
+        match env::current_dir() {
+            Ok(dir) => output(format!("{}\n", dir.display())),
+            Err(e) => {
+                eprintln!("pwd: {}", describe(&e));
+                failed(1)
+            }
+        }
+    }
+
+    /// `pushd dir` changes to `dir`, saving the current directory on the
+    /// stack. Without a directory, it swaps the current directory with the
+    /// one on top of the stack.
+    pub(super) fn builtin_pushd(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let current = match env::current_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                eprintln!("pushd: {}", describe(&e));
+                return failed(1);
+            }
+        };
+
+        let target = match args.as_slice() {
+            [] => match self.dir_stack.pop() {
+                Some(top) => top,
+                None => {
+                    eprintln!("pushd: no other directory");
+                    return failed(1);
+                }
+            },
+            [arg] => expand_tilde(&arg.to_native_string()),
+            _ => {
+                eprintln!("pushd: too many arguments");
+                return failed(1);
+            }
+        };
+
+        if let Err(e) = change_dir(&target) {
+            eprintln!("pushd: {}: {}", target.display(), describe(&e));
+            if args.is_empty() {
+                self.dir_stack.push(target);
+            }
+            return failed(1);
+        }
+
+        self.dir_stack.push(current);
+        self.builtin_dirs(Vec::new())
+    }
+
+    /// `popd` changes to the directory on top of the stack, removing it.
+    pub(super) fn builtin_popd(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        if !args.is_empty() {
+            eprintln!("popd: too many arguments");
+            return failed(1);
+        }
+
+        let Some(target) = self.dir_stack.pop() else {
+            eprintln!("popd: directory stack empty");
+            return failed(1);
+        };
+
+        if let Err(e) = change_dir(&target) {
+            eprintln!("popd: {}: {}", target.display(), describe(&e));
+            return failed(1);
+        }
+
+        self.builtin_dirs(Vec::new())
+    }
+
+    /// `dirs` shows the current directory followed by the stack, top first.
+    /// `dirs -c` clears the stack.
+    pub(super) fn builtin_dirs(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        match args.first().map(|arg| arg.to_native_string()).as_deref() {
+            None => {}
+            Some("-c") => {
+                self.dir_stack.clear();
+                return (Value::None, 0);
+            }
+            Some(arg) => {
+                eprintln!("dirs: unexpected argument `{}`", arg);
+                return failed(1);
+            }
+        }
+
+        let current = env::current_dir().unwrap_or_default();
+        let dirs: Vec<String> = std::iter::once(&current)
+            .chain(self.dir_stack.iter().rev())
+            .map(|dir| abbreviate(dir))
+            .collect();
+
+        output(format!("{}\n", dirs.join(" ")))
+    }
+}
@@ -0,0 +1,207 @@
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+
+use crate::vm::{
+    jobs::JobState,
+    signals,
+    value::{ivalue, Value},
+    vm::Vm,
+};
+
+use super::{failed, output};
+
+/// Something to signal or wait for: a job given as `%n`, or a process ID.
+enum Target {
+    Job(usize),
+    Process(Pid),
+}
+
+impl Vm {
+    fn target(&self, builtin: &str, arg: &str) -> Option<Target> {
+        match arg.strip_prefix('%') {
+            Some(spec) => {
+                let index = self.find_job(spec);
+                if index.is_none() {
+                    eprintln!("{}: {}: no such job", builtin, arg);
+                }
+                index.map(Target::Job)
+            }
+            None => {
+                let pid = arg.parse::<i32>().ok();
+                if pid.is_none() {
+                    eprintln!("{}: {}: not a job or process ID", builtin, arg);
+                }
+                pid.map(|pid| Target::Process(Pid::from_raw(pid)))
+            }
+        }
+    }
+
+    /// The job that `fg` or `bg` act on: the one given, or else the current
+    /// job.
+    fn job_argument(&mut self, builtin: &str, args: &[Value]) -> Option<usize> {
+        if !self.options.monitor {
+            eprintln!("{}: no job control", builtin);
+            return None;
+        }
+
+        self.update_jobs();
+        let spec = args.first().map(|arg| arg.to_native_string());
+        let spec = spec.as_deref().unwrap_or("%");
+
+        let index = self.find_job(spec.strip_prefix('%').unwrap_or(spec));
+        if index.is_none() {
+            eprintln!("{}: {}: no such job", builtin, spec);
+        }
+        index
+    }
+
+    /// `jobs [-p]` lists the jobs, or with `-p` just their process groups.
+    pub(super) fn builtin_jobs(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let groups_only = args.iter().any(|arg| arg.to_native_string() == "-p");
+
+        self.update_jobs();
+        let mut text = String::new();
+        for index in 0..self.jobs.len() {
+            if groups_only {
+                text += &format!("{}\n", self.jobs[index].group);
+            } else {
+                text += &format!("{}\n", self.job_line(index));
+            }
+        }
+
+        // Listing a finished job is as good as being told about it.
+        self.forget_finished_jobs();
+        output(text)
+    }
+
+    /// `fg [%n]` continues a job in the foreground and waits for it.
+    pub(super) fn builtin_fg(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let Some(index) = self.job_argument("fg", &args) else {
+            return failed(1);
+        };
+
+        eprintln!("{}", self.jobs[index].command);
+        (Value::None, self.foreground_job(index))
+    }
+
+    /// `bg [%n]` continues a stopped job in the background.
+    pub(super) fn builtin_bg(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let Some(index) = self.job_argument("bg", &args) else {
+            return failed(1);
+        };
+
+        let job = &self.jobs[index];
+        if let JobState::Done(_) = job.state() {
+            eprintln!("bg: job {} has already finished", job.id);
+            return failed(1);
+        }
+
+        self.background_job(index);
+        eprintln!("{}", self.job_line(index));
+        (Value::None, 0)
+    }
+
+    /// `wait [%n | pid]...` waits for the given jobs to finish, or for every
+    /// job if none are given, returning the status of the last.
+    pub(super) fn builtin_wait(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        if args.is_empty() {
+            let ids: Vec<usize> = self.jobs.iter().map(|job| job.id).collect();
+            for id in ids {
+                let Some(index) = self.jobs.iter().position(|job| job.id == id) else {
+                    continue;
+                };
+                let status = self.wait_for_job(index);
+                // Waiting is cut short by a signal, so that its trap can run.
+                if signals::any_pending() {
+                    return (Value::None, status);
+                }
+            }
+            return (Value::None, 0);
+        }
+
+        let mut status = 0;
+        for arg in args.iter().map(|arg| arg.to_native_string()) {
+            let index = match self.target("wait", &arg) {
+                Some(Target::Job(index)) => index,
+                Some(Target::Process(pid)) => match self.find_job_by_pid(pid) {
+                    Some(index) => index,
+                    None => {
+                        eprintln!("wait: pid {} is not a child of this shell", pid);
+                        status = 127;
+                        continue;
+                    }
+                },
+                None => {
+                    status = 127;
+                    continue;
+                }
+            };
+            status = self.wait_for_job(index);
+            if signals::any_pending() {
+                break;
+            }
+        }
+
+        (Value::None, status)
+    }
+
+    /// `kill [-SIGNAL] (%n | pid)...` sends a signal, SIGTERM by default, to
+    /// every process in a job or to a single process. `kill -l` lists the
+    /// signals.
+    pub(super) fn builtin_kill(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_native_string()).collect();
+
+        if args.first().is_some_and(|arg| arg == "-l") {
+            let names: Vec<&str> = Signal::iterator().map(|s| s.as_str()).collect();
+            return output(format!("{}\n", names.join(" ")));
+        }
+
+        let mut sig = Signal::SIGTERM;
+        if let Some(name) = args.first().and_then(|arg| arg.strip_prefix('-')) {
+            match signals::parse(name) {
+                Some(s) => sig = s,
+                None => {
+                    eprintln!("kill: {}: unknown signal", name);
+                    return failed(2);
+                }
+            }
+            args.remove(0);
+        }
+
+        if args.is_empty() {
+            eprintln!("kill: expected a job or process ID");
+            return failed(2);
+        }
+
+        let mut status = 0;
+        for arg in &args {
+            let sent = match self.target("kill", arg) {
+                Some(Target::Job(index)) => {
+                    let job = &self.jobs[index];
+                    let sent = signal::killpg(job.group, sig);
+                    // A stopped job can't act on the signal until it runs.
+                    if job.state() == JobState::Stopped
+                        && matches!(sig, Signal::SIGTERM | Signal::SIGHUP)
+                    {
+                        let _ = signal::killpg(job.group, Signal::SIGCONT);
+                    }
+                    sent
+                }
+                Some(Target::Process(pid)) => signal::kill(pid, sig),
+                None => {
+                    status = 1;
+                    continue;
+                }
+            };
+
+            if let Err(e) = sent {
+                eprintln!("kill: {}: {}", arg, e.desc());
+                status = 1;
+            }
+        }
+
+        (Value::None, status)
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diagnostic::{self, Location},
+    scan::tokens::TokenType,
+    vm::{
+        local::ScopeSearch,
+        runtime,
+        value::{ivalue, Value},
+        vm::{Vm, VmError},
+    },
+};
+
+use super::{describe, failed, output};
+
+/// Whether `path` is a file that can be run.
+pub fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Finds the executable that running `name` would start, searching `$PATH`
+/// unless the name is already a path.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+impl Vm {
+    /// `echo [-n] args...` shows its arguments separated by spaces. `-n`
+    /// leaves off the newline.
+    pub(super) fn builtin_echo(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let mut words = runtime::arguments(&args).into_iter().peekable();
+        let newline = words.next_if(|word| word == "-n").is_none();
+
+        let mut text = words.collect::<Vec<String>>().join(" ");
+        if newline {
+            text.push('\n');
+        }
+        output(text)
+    }
+
+    /// `exit [code]` stops the shell, with the given status or else that of
+    /// the last command.
+    pub(super) fn builtin_exit(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let code = match args.first() {
+            Some(code) => match code.to_ivalue() {
+                Ok(code) => code,
+                Err(e) => {
+                    eprintln!("exit: {}", e);
+                    2
+                }
+            },
+            None => self.last_status(),
+        };
+
+        self.halt(VmError::Exit(code));
+        (Value::None, code)
+    }
+
+    /// `set -e` / `set +e`, or `set -o errexit` / `set +o errexit`, turn an
+    /// option on or off.
+    pub(super) fn builtin_set(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let mut args = args.iter().map(|arg| arg.to_native_string());
+
+        while let Some(arg) = args.next() {
+            let (on, flags) = match arg.split_at_checked(1) {
+                Some(("-", flags)) => (true, flags),
+                Some(("+", flags)) => (false, flags),
+                _ => {
+                    eprintln!("set: unexpected argument `{}`", arg);
+                    return failed(2);
+                }
+            };
+
+            let names: Vec<String> = if flags == "o" {
+                args.next().into_iter().collect()
+            } else {
+                flags.chars().map(|c| c.to_string()).collect()
+            };
+
+            for name in names {
+                if !self.options.set(&name, on) {
+                    eprintln!("set: unknown option `{}`", name);
+                    return failed(2);
+                }
+            }
+        }
+
+        (Value::None, 0)
+    }
+
+    /// `shift [n]` drops the first `n` arguments, 1 by default, so that `@2`
+    /// becomes `@1` and so on. In a function invoked as a command it's the
+    /// function's arguments that are shifted.
+    pub(super) fn builtin_shift(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let count = match args.first().map(Value::to_ivalue) {
+            None => 1,
+            Some(Ok(count)) if count >= 0 => count as usize,
+            Some(_) => {
+                eprintln!("shift: expected a number of arguments to shift");
+                return failed(2);
+            }
+        };
+
+        let (depth, mut positional) = self.positional().unwrap_or_default();
+        if count > positional.len() {
+            eprintln!(
+                "shift: can't shift {} of {} arguments",
+                count,
+                positional.len()
+            );
+            return failed(1);
+        }
+
+        positional.drain(..count);
+        self.set_positional(depth, positional);
+        (Value::None, 0)
+    }
+
+    /// `type names...` shows whether each name is a builtin or, if not, which
+    /// executable it runs.
+    pub(super) fn builtin_type(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let mut text = String::new();
+        let mut status = 0;
+
+        for name in args.iter().map(|arg| arg.to_native_string()) {
+            if self.function(&name).is_some() {
+                text += &format!("{} is a function\n", name);
+            } else if self.is_builtin(&name) {
+                text += &format!("{} is a shell builtin\n", name);
+            } else if let Some(path) = find_executable(&name) {
+                text += &format!("{} is {}\n", name, path.display());
+            } else {
+                eprintln!("type: {}: not found", name);
+                status = 1;
+            }
+        }
+
+        (Value::String(text), status)
+    }
+
+    /// `export NAME=value` sets an environment variable, and `export NAME`
+    /// copies the local `@NAME` into one. With no arguments, every variable
+    /// is shown.
+    pub(super) fn builtin_export(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        if args.is_empty() {
+            let mut vars: Vec<(String, String)> = env::vars().collect();
+            vars.sort();
+            let text: String = vars
+                .iter()
+                .map(|(name, value)| format!("{}={}\n", name, value))
+                .collect();
+            return output(text);
+        }
+
+        for arg in args.iter().map(|arg| arg.to_native_string()) {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => {
+                    let local = self.get_local(&arg, ScopeSearch::AllScopes);
+                    (arg.clone(), local.map(|l| l.value.to_native_string()))
+                }
+            };
+
+            if name.is_empty() || name.contains('\0') {
+                eprintln!("export: `{}` is not a valid name", arg);
+                return failed(1);
+            }
+
+            if let Some(value) = value {
+                env::set_var(name, value);
+            }
+        }
+
+        (Value::None, 0)
+    }
+
+    /// `source file` runs a script in the current shell, so that the
+    /// variables and functions it defines are kept.
+    pub(super) fn builtin_source(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let Some(name) = args.first().map(|arg| arg.to_native_string()) else {
+            eprintln!("source: expected a file name");
+            return failed(2);
+        };
+
+        let source = match fs::read_to_string(&name) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("source: {}: {}", name, describe(&e));
+                return failed(1);
+            }
+        };
+
+        self.run_source(&name, source)
+    }
+
+    /// Compiles and runs code in the current shell, reporting any errors
+    /// against `name`.
+    pub(in crate::vm) fn run_source(&mut self, name: &str, source: String) -> (Value, ivalue) {
+        let mut compiler = self.compiler(source.clone());
+        if let Err(e) = compiler.compile_to(TokenType::EndOfFile) {
+            let location = compiler.error_location(&e);
+            eprint!("{}", diagnostic::render(name, &source, &location, &e));
+            return failed(2);
+        }
+
+        match self.run_nested(compiler.into_chunk()) {
+            Ok(_) => (Value::None, self.last_status()),
+            // These stop the shell, not just the script.
+            Err((
+                e @ (VmError::Exit(_) | VmError::CommandFailed(..) | VmError::Interrupted),
+                _,
+            )) => {
+                let status = e.exit_status();
+                self.halt(e);
+                failed(status)
+            }
+            Err((e, position)) => {
+                match position {
+                    Some((line, column)) => {
+                        let location = Location {
+                            line,
+                            column,
+                            length: 1,
+                        };
+                        eprint!("{}", diagnostic::render(name, &source, &location, &e));
+                    }
+                    None => eprint!("{}", diagnostic::render_plain(&e)),
+                }
+                failed(e.exit_status())
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::vm::{
+    signals,
+    value::{ivalue, Value},
+    vm::Vm,
+};
+
+use super::{failed, output};
+
+impl Vm {
+    /// `trap code signals...` runs the code whenever one of the signals
+    /// arrives, or for `EXIT` as the shell exits. Code of `-` removes the
+    /// trap and empty code ignores the signal. With no arguments, the traps
+    /// are listed.
+    pub(super) fn builtin_trap(&mut self, args: Vec<Value>) -> (Value, ivalue) {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_native_string()).collect();
+
+        if args.is_empty() || args[0] == "-p" {
+            let mut traps: Vec<(&String, &String)> = self.traps.iter().collect();
+            traps.sort();
+            let text: String = traps
+                .iter()
+                .map(|(name, code)| format!("trap -- '{}' {}\n", code, name))
+                .collect();
+            return output(text);
+        }
+
+        // `trap INT` on its own removes the trap, as `trap - INT` does.
+        let code = if args.len() == 1 {
+            "-".to_owned()
+        } else {
+            args.remove(0)
+        };
+
+        let mut status = 0;
+        for name in args {
+            let sig = match name.to_uppercase().as_str() {
+                "EXIT" | "0" => None,
+                _ => match signals::parse(&name) {
+                    Some(sig) => Some(sig),
+                    None => {
+                        eprintln!("trap: {}: unknown signal", name);
+                        status = 1;
+                        continue;
+                    }
+                },
+            };
+            let trap_name = sig.map_or("EXIT", signals::trap_name).to_owned();
+
+            if code == "-" {
+                self.traps.remove(&trap_name);
+                if let Some(sig) = sig {
+                    signals::restore(sig);
+                }
+                continue;
+            }
+
+            if let Some(sig) = sig {
+                if signals::catch(sig).is_err() {
+                    eprintln!("trap: {} can't be trapped", sig);
+                    status = 1;
+                    continue;
+                }
+            }
+            self.traps.insert(trap_name, code.clone());
+        }
+
+        if status == 0 {
+            (Value::None, 0)
+        } else {
+            failed(status)
+        }
+    }
+}
@@ -1,3 +1,4 @@
 pub mod bytecode_chunk;
 pub mod disassemble;
 pub mod reader;
+pub mod verify;
@@ -1,13 +1,30 @@
-use crate::vm::{
-    op::Op,
-    value::{ivalue, Value},
-};
+use crate::vm::{op::Op, value::ivalue};
+
+/// A function compiled into a chunk: its parameter count and the offset of
+/// the first instruction of its body.
+#[derive(Debug, Clone)]
+pub struct FunctionDefinition {
+    pub arity: u8,
+    pub offset: usize,
+}
+
+/// The source position of the code from `offset` up to the next entry.
+#[derive(Debug, Clone, Copy)]
+pub struct SourcePosition {
+    pub offset: usize,
+    pub line: i64,
+    pub column: i64,
+}
 
 #[derive(Debug, Clone)]
 pub struct ByteCodeChunk {
     pub content: Vec<u8>,
     pub strings: Vec<String>,
-    pub functions: Vec<Value>,
+    pub functions: Vec<FunctionDefinition>,
+    pub positions: Vec<SourcePosition>,
+
+    /// The (line, column) that ops written now were compiled from.
+    position: (i64, i64),
 }
 
 impl ByteCodeChunk {
@@ -16,6 +33,8 @@ impl ByteCodeChunk {
             content: Vec::new(),
             strings: Vec::new(),
             functions: Vec::new(),
+            positions: Vec::new(),
+            position: (0, 0),
         }
     }
 
@@ -26,9 +45,33 @@ impl ByteCodeChunk {
 
     #[inline(always)]
     pub fn write_op(&mut self, v: Op) {
+        let (line, column) = self.position;
+        if self
+            .positions
+            .last()
+            .is_none_or(|p| p.line != line || p.column != column)
+        {
+            self.positions.push(SourcePosition {
+                offset: self.content.len(),
+                line,
+                column,
+            });
+        }
+
         self.content.extend(&[v as u8])
     }
 
+    pub fn set_position(&mut self, line: i64, column: i64) {
+        self.position = (line, column);
+    }
+
+    /// The (line, column) that the op at `offset` was compiled from.
+    pub fn position_at(&self, offset: usize) -> Option<(i64, i64)> {
+        let index = self.positions.partition_point(|p| p.offset <= offset);
+        let position = self.positions.get(index.checked_sub(1)?)?;
+        Some((position.line, position.column))
+    }
+
     #[inline(always)]
     pub fn write_ivalue(&mut self, v: ivalue) {
         self.content.extend(&ivalue::to_ne_bytes(v))
@@ -49,8 +92,8 @@ impl ByteCodeChunk {
         }
     }
 
-    pub fn add_function(&mut self, v: Value) -> usize {
-        self.functions.push(v);
+    pub fn add_function(&mut self, arity: u8, offset: usize) -> usize {
+        self.functions.push(FunctionDefinition { arity, offset });
         self.functions.len() - 1
     }
 
@@ -54,10 +54,8 @@ impl ByteCodeChunk {
         }
     }
 
-    pub fn disassemble(&self) -> Result<String, DisassembleError> {
-        let mut reader = ByteCodeChunkReader::new(self);
-
-        let op_funcs: HashMap<Op, (&str, DisassembleFn)> = [
+    fn op_table() -> HashMap<Op, (&'static str, DisassembleFn)> {
+        [
             (
                 Op::Return,
                 ("RTS", ByteCodeChunk::disassemble_simple as DisassembleFn),
@@ -101,10 +99,21 @@ impl ByteCodeChunk {
             (Op::Pipe, ("PIP", ByteCodeChunk::disassemble_simple)),
             (Op::Swap, ("SWP", ByteCodeChunk::disassemble_simple)),
             (Op::Negate, ("NEG", ByteCodeChunk::disassemble_simple)),
+            (Op::Not, ("NOT", ByteCodeChunk::disassemble_simple)),
+            (Op::Range, ("RNG", ByteCodeChunk::disassemble_simple)),
             (
                 Op::Command,
                 ("CMD", ByteCodeChunk::disassemble_string_const),
             ),
+            (Op::Redirect, ("RED", ByteCodeChunk::disassemble_1::<u8>)),
+            (Op::Glob, ("GLB", ByteCodeChunk::disassemble_simple)),
+            (Op::Background, ("BKG", ByteCodeChunk::disassemble_simple)),
+            (Op::List, ("LIS", ByteCodeChunk::disassemble_simple)),
+            (Op::Index, ("IDX", ByteCodeChunk::disassemble_simple)),
+            (Op::Slice, ("SLC", ByteCodeChunk::disassemble_simple)),
+            (Op::Length, ("LEN", ByteCodeChunk::disassemble_simple)),
+            (Op::Map, ("MAP", ByteCodeChunk::disassemble_simple)),
+            (Op::Contains, ("HAS", ByteCodeChunk::disassemble_simple)),
             (
                 Op::BranchIfFalse,
                 ("BRF", ByteCodeChunk::disassemble_1::<usize>),
@@ -114,6 +123,12 @@ impl ByteCodeChunk {
                 Op::BranchBack,
                 ("BRB", ByteCodeChunk::disassemble_1::<usize>),
             ),
+            (Op::Iterate, ("ITR", ByteCodeChunk::disassemble_simple)),
+            (
+                Op::IterateNext,
+                ("NXT", ByteCodeChunk::disassemble_1::<usize>),
+            ),
+            (Op::IterateEnd, ("ITE", ByteCodeChunk::disassemble_simple)),
             (
                 Op::SysCall,
                 ("SYS", ByteCodeChunk::disassemble_string_const),
@@ -121,27 +136,60 @@ impl ByteCodeChunk {
             (Op::BeginScope, ("BSC", ByteCodeChunk::disassemble_simple)),
             (Op::EndScope, ("ESC", ByteCodeChunk::disassemble_simple)),
             (Op::Equal, ("EQL", ByteCodeChunk::disassemble_simple)),
+            (Op::NotEqual, ("NEQ", ByteCodeChunk::disassemble_simple)),
+            (Op::Less, ("LST", ByteCodeChunk::disassemble_simple)),
+            (Op::LessEqual, ("LTE", ByteCodeChunk::disassemble_simple)),
+            (Op::Greater, ("GRT", ByteCodeChunk::disassemble_simple)),
+            (Op::GreaterEqual, ("GTE", ByteCodeChunk::disassemble_simple)),
+            (Op::Compare, ("CMP", ByteCodeChunk::disassemble_simple)),
             (
                 Op::FunctionDefinition,
                 ("DFN", ByteCodeChunk::disassemble_1::<usize>),
             ),
+            (Op::Call, ("CAL", ByteCodeChunk::disassemble_1::<u8>)),
         ]
         .into_iter()
         .map(|(op, (name, func))| (op, (name, func as DisassembleFn)))
-        .collect();
+        .collect()
+    }
 
-        let mut output = String::new();
+    /// Disassembles the op that `reader` is at, and its operands.
+    fn disassemble_op(
+        &self,
+        reader: &mut ByteCodeChunkReader,
+        op_funcs: &HashMap<Op, (&str, DisassembleFn)>,
+    ) -> Result<Option<String>, DisassembleError> {
+        let Some(byte) = reader.next::<u8>() else {
+            return Ok(None);
+        };
+
+        let mut line = format!("{:08} [{:02x}] ", reader.get_offset() - OP_SIZE, byte);
+        match Op::try_from(byte).ok().and_then(|op| op_funcs.get(&op)) {
+            Some((name, func)) => line += &func(self, reader, name)?,
+            None => line += "???",
+        }
+        Ok(Some(line))
+    }
+
+    pub fn disassemble(&self) -> Result<String, DisassembleError> {
+        let mut reader = ByteCodeChunkReader::new(self);
+        let op_funcs = ByteCodeChunk::op_table();
 
-        while let Some(op) = reader.next::<Op>() {
-            output += &format!("{:08} [{:02x}] ", reader.get_offset() - OP_SIZE, op as u8);
-            if let Some((name, func)) = op_funcs.get(&op) {
-                output += &func(self, &mut reader, name)?;
-                output += "\n";
-            } else {
-                output += "???\n";
-            }
+        let mut output = String::new();
+        while let Some(line) = self.disassemble_op(&mut reader, &op_funcs)? {
+            output += &line;
+            output += "\n";
         }
 
         Ok(output)
     }
+
+    /// Disassembles the single op at `offset`, for tracing execution.
+    pub fn disassemble_at(&self, offset: usize) -> Result<String, DisassembleError> {
+        let mut reader = ByteCodeChunkReader::new(self);
+        reader.seek(offset);
+
+        self.disassemble_op(&mut reader, &ByteCodeChunk::op_table())?
+            .ok_or(DisassembleError::ChunkTooSmall)
+    }
 }
@@ -32,6 +32,11 @@ impl ByteCodeChunkReader {
         }
     }
 
+    /// Moves to `offset` bytes from the start of the chunk.
+    pub fn seek(&mut self, offset: usize) {
+        self.ptr = self.first.wrapping_add(offset);
+    }
+
     pub fn get_offset(&self) -> usize {
         self.ptr.wrapping_sub(self.first as usize) as usize
     }
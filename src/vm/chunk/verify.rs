@@ -0,0 +1,266 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::vm::{
+    op::{Op, OP_SIZE},
+    redirect::RedirectKind,
+    value::{fvalue, ivalue},
+};
+
+use super::bytecode_chunk::ByteCodeChunk;
+
+/// Why a chunk was rejected by [`ByteCodeChunk::verify`]. Each error holds the
+/// offset of the op at fault.
+#[derive(Debug)]
+pub enum VerifyError {
+    UnknownOp(usize, u8),
+    TruncatedOperand(usize, Op),
+    UnknownString(usize, usize),
+    UnknownFunction(usize, usize),
+    InvalidBool(usize, u8),
+    InvalidRedirect(usize, u8),
+    /// A branch, or a function, that doesn't start at an op.
+    InvalidTarget(usize),
+    MissingReturn,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::UnknownOp(at, op) => write!(f, "unknown op {:02x} at {:08}", op, at),
+            VerifyError::TruncatedOperand(at, op) => {
+                write!(f, "operand of {:?} at {:08} is cut short", op, at)
+            }
+            VerifyError::UnknownString(at, id) => {
+                write!(f, "unknown string {} used at {:08}", id, at)
+            }
+            VerifyError::UnknownFunction(at, id) => {
+                write!(f, "unknown function {} used at {:08}", id, at)
+            }
+            VerifyError::InvalidBool(at, v) => write!(f, "invalid bool {} at {:08}", v, at),
+            VerifyError::InvalidRedirect(at, v) => {
+                write!(f, "invalid redirect kind {} at {:08}", v, at)
+            }
+            VerifyError::InvalidTarget(at) => {
+                write!(f, "jump from {:08} doesn't land on an op", at)
+            }
+            VerifyError::MissingReturn => write!(f, "chunk doesn't end with a return"),
+        }
+    }
+}
+
+/// Where an op with a jump operand goes.
+enum Target {
+    Forward(usize),
+    Back(usize),
+}
+
+impl ByteCodeChunk {
+    fn read_operand<const N: usize>(&self, at: usize, op: Op) -> Result<[u8; N], VerifyError> {
+        self.content
+            .get(at + OP_SIZE..at + OP_SIZE + N)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(VerifyError::TruncatedOperand(at, op))
+    }
+
+    /// Checks that the chunk can be run without reading anything that isn't
+    /// there: every op is known, its operands are whole and refer to strings
+    /// and functions in the chunk, and every jump lands on an op.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut ops = HashSet::new();
+        let mut targets = Vec::new();
+        let mut last = None;
+
+        let mut at = 0;
+        while at < self.content.len() {
+            let byte = self.content[at];
+            let op = Op::try_from(byte).map_err(|byte| VerifyError::UnknownOp(at, byte))?;
+
+            let size = match op {
+                Op::IntConstant => self.read_operand::<{ size_of::<ivalue>() }>(at, op)?.len(),
+                Op::FloatConstant => self.read_operand::<{ size_of::<fvalue>() }>(at, op)?.len(),
+                Op::BoolConstant => {
+                    let [v] = self.read_operand::<1>(at, op)?;
+                    if v > 1 {
+                        return Err(VerifyError::InvalidBool(at, v));
+                    }
+                    1
+                }
+                Op::Redirect => {
+                    let [v] = self.read_operand::<1>(at, op)?;
+                    if v > RedirectKind::All as u8 {
+                        return Err(VerifyError::InvalidRedirect(at, v));
+                    }
+                    1
+                }
+                Op::Call => self.read_operand::<1>(at, op)?.len(),
+                Op::StringConstant
+                | Op::GetEnv
+                | Op::SetEnv
+                | Op::DefineLocal
+                | Op::PinLocal
+                | Op::GetLocal
+                | Op::SetLocal
+                | Op::Command
+                | Op::SysCall => {
+                    let id = usize::from_ne_bytes(self.read_operand(at, op)?);
+                    if id >= self.strings.len() {
+                        return Err(VerifyError::UnknownString(at, id));
+                    }
+                    size_of::<usize>()
+                }
+                Op::FunctionDefinition => {
+                    let id = usize::from_ne_bytes(self.read_operand(at, op)?);
+                    if id >= self.functions.len() {
+                        return Err(VerifyError::UnknownFunction(at, id));
+                    }
+                    size_of::<usize>()
+                }
+                Op::Branch | Op::BranchIfFalse | Op::IterateNext => {
+                    let dist = usize::from_ne_bytes(self.read_operand(at, op)?);
+                    targets.push((at, Target::Forward(dist)));
+                    size_of::<usize>()
+                }
+                Op::BranchBack => {
+                    let dist = usize::from_ne_bytes(self.read_operand(at, op)?);
+                    targets.push((at, Target::Back(dist)));
+                    size_of::<usize>()
+                }
+                _ => 0,
+            };
+
+            ops.insert(at);
+            last = Some(op);
+            at += OP_SIZE + size;
+        }
+
+        if last != Some(Op::Return) {
+            return Err(VerifyError::MissingReturn);
+        }
+
+        // Jumps are measured from the end of the op making them.
+        for (at, target) in targets {
+            let end = at + OP_SIZE + size_of::<usize>();
+            let landing = match target {
+                Target::Forward(dist) => end.checked_add(dist),
+                Target::Back(dist) => end.checked_sub(dist),
+            };
+            if !landing.is_some_and(|l| ops.contains(&l)) {
+                return Err(VerifyError::InvalidTarget(at));
+            }
+        }
+
+        for function in &self.functions {
+            if !ops.contains(&function.offset) {
+                return Err(VerifyError::InvalidTarget(function.offset));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chunk of the given ops, each followed by its operand bytes.
+    fn chunk(ops: &[(Op, &[u8])]) -> ByteCodeChunk {
+        let mut chunk = ByteCodeChunk::new();
+        for (op, operand) in ops {
+            chunk.write_op(*op);
+            chunk.content.extend_from_slice(operand);
+        }
+        chunk
+    }
+
+    fn id(v: usize) -> [u8; size_of::<usize>()] {
+        v.to_ne_bytes()
+    }
+
+    #[test]
+    fn accepts_well_formed_chunk() {
+        let mut chunk = chunk(&[
+            (Op::IntConstant, &1i32.to_ne_bytes()),
+            (Op::BranchIfFalse, &id(1)),
+            (Op::Pop, &[]),
+            (Op::Swap, &[]),
+            (Op::BranchBack, &id(OP_SIZE + size_of::<usize>())),
+            (Op::Return, &[]),
+        ]);
+        chunk.add_function(0, 0);
+        assert!(chunk.verify().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_op() {
+        let mut chunk = chunk(&[(Op::Pop, &[])]);
+        chunk.content.extend_from_slice(&[2, Op::Return as u8]);
+        assert!(matches!(chunk.verify(), Err(VerifyError::UnknownOp(1, 2))));
+    }
+
+    #[test]
+    fn rejects_truncated_operand() {
+        let chunk = chunk(&[(Op::Return, &[]), (Op::IntConstant, &[0, 0])]);
+        assert!(matches!(
+            chunk.verify(),
+            Err(VerifyError::TruncatedOperand(1, Op::IntConstant))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_string() {
+        let chunk = chunk(&[(Op::GetLocal, &id(0)), (Op::Return, &[])]);
+        assert!(matches!(
+            chunk.verify(),
+            Err(VerifyError::UnknownString(0, 0))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_function() {
+        let chunk = chunk(&[(Op::FunctionDefinition, &id(3)), (Op::Return, &[])]);
+        assert!(matches!(
+            chunk.verify(),
+            Err(VerifyError::UnknownFunction(0, 3))
+        ));
+    }
+
+    #[test]
+    fn rejects_branch_into_an_operand() {
+        // Lands on the second byte of the int's operand.
+        let chunk = chunk(&[
+            (Op::Branch, &id(2)),
+            (Op::IntConstant, &1i32.to_ne_bytes()),
+            (Op::Return, &[]),
+        ]);
+        assert!(matches!(chunk.verify(), Err(VerifyError::InvalidTarget(0))));
+    }
+
+    #[test]
+    fn rejects_branch_out_of_the_chunk() {
+        let chunk = chunk(&[(Op::BranchBack, &id(100)), (Op::Return, &[])]);
+        assert!(matches!(chunk.verify(), Err(VerifyError::InvalidTarget(0))));
+    }
+
+    #[test]
+    fn rejects_function_starting_mid_op() {
+        let mut chunk = chunk(&[(Op::IntConstant, &1i32.to_ne_bytes()), (Op::Return, &[])]);
+        chunk.add_function(0, 2);
+        assert!(matches!(chunk.verify(), Err(VerifyError::InvalidTarget(2))));
+    }
+
+    #[test]
+    fn rejects_missing_return() {
+        assert!(matches!(
+            chunk(&[(Op::Pop, &[])]).verify(),
+            Err(VerifyError::MissingReturn)
+        ));
+        assert!(matches!(
+            ByteCodeChunk::new().verify(),
+            Err(VerifyError::MissingReturn)
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+use std::cmp::Ordering;
+
+use super::{
+    op::Op,
+    value::{fvalue, ivalue, Value},
+    vm::{Vm, VmError},
+};
+
+impl Vm {
+    /// Pops two values and tests them for equality. Numbers, and strings
+    /// that read as them, are compared as numbers, and any other string as
+    /// text, whichever side it's on.
+    pub(super) fn equals(&mut self) -> Result<bool, VmError> {
+        let right = self.pop_stack()?;
+        let left = self.pop_stack()?;
+
+        if let Some((x, y)) = numbers(&left, &right) {
+            return Ok(order_numbers(x, y) == Some(Ordering::Equal));
+        }
+
+        Ok(match (left, right) {
+            (Value::None, other) | (other, Value::None) => matches!(other, Value::None),
+            (left @ (Value::Command(..) | Value::Pipeline(..) | Value::Function(..)), right)
+            | (left, right @ (Value::Command(..) | Value::Pipeline(..) | Value::Function(..))) => {
+                return Err(VmError::InvalidOperands(
+                    Op::Equal,
+                    left.type_name(),
+                    right.type_name(),
+                ))
+            }
+            (Value::Bool(x), other) | (other, Value::Bool(x)) => other.to_native_bool() == x,
+            (left @ (Value::Map(_) | Value::List(_) | Value::Range(..)), right)
+            | (left, right @ (Value::Map(_) | Value::List(_) | Value::Range(..))) => left == right,
+            (left, right) => left.to_native_string() == right.to_native_string(),
+        })
+    }
+
+    /// Pops two values and orders the left-hand one against the right-hand
+    /// one, comparing numbers and text as [`Vm::equals`] does.
+    pub(super) fn compare(&mut self) -> Result<Ordering, VmError> {
+        let right = self.pop_stack()?;
+        let left = self.pop_stack()?;
+
+        let ordering = match numbers(&left, &right) {
+            Some((x, y)) => order_numbers(x, y),
+            None => match (left, right) {
+                (Value::None, Value::None) => Some(Ordering::Equal),
+                (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(&y)),
+                (Value::Bool(x), other) => Some(x.cmp(&other.to_native_bool())),
+                (other, Value::Bool(y)) => Some(other.to_native_bool().cmp(&y)),
+                (
+                    left @ (Value::Int(_) | Value::Float(_) | Value::String(_)),
+                    right @ (Value::Int(_) | Value::Float(_) | Value::String(_)),
+                ) => Some(left.to_native_string().cmp(&right.to_native_string())),
+                (left, right) => {
+                    return Err(VmError::InvalidOperands(
+                        Op::Compare,
+                        left.type_name(),
+                        right.type_name(),
+                    ))
+                }
+            },
+        };
+
+        // Only NaN fails to order.
+        ordering.ok_or(VmError::Unordered)
+    }
+}
+
+/// The number a value is, or that a string reads as, e.g. `"10"`.
+fn number(value: &Value) -> Option<Value> {
+    match value {
+        Value::Int(_) | Value::Float(_) => Some(value.clone()),
+        Value::String(s) => s
+            .parse::<ivalue>()
+            .map(Value::Int)
+            .or_else(|_| s.parse::<fvalue>().map(Value::Float))
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Both values as numbers, if both are, or read as, numbers.
+fn numbers(left: &Value, right: &Value) -> Option<(Value, Value)> {
+    Some((number(left)?, number(right)?))
+}
+
+/// Orders two numbers, or gives `None` if either is NaN.
+fn order_numbers(x: Value, y: Value) -> Option<Ordering> {
+    match (x, y) {
+        (Value::Int(x), Value::Int(y)) => Some(x.cmp(&y)),
+        (x, y) => x.to_fvalue().ok()?.partial_cmp(&y.to_fvalue().ok()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(x: ivalue) -> Value {
+        Value::Int(x)
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    fn equals(left: Value, right: Value) -> Result<bool, VmError> {
+        let mut vm = Vm::new();
+        vm.push_stack(left);
+        vm.push_stack(right);
+        vm.equals()
+    }
+
+    fn compare(left: Value, right: Value) -> Result<Ordering, VmError> {
+        let mut vm = Vm::new();
+        vm.push_stack(left);
+        vm.push_stack(right);
+        vm.compare()
+    }
+
+    /// Checks `left == right` and `right == left` both give `expected`.
+    fn assert_equals(left: Value, right: Value, expected: bool) {
+        assert_eq!(equals(left.clone(), right.clone()).ok(), Some(expected));
+        assert_eq!(equals(right, left).ok(), Some(expected));
+    }
+
+    #[test]
+    fn equals_compares_numbers_as_numbers() {
+        assert_equals(Value::Float(1.5), int(1), false);
+        assert_equals(int(1), Value::Float(1.0), true);
+        assert_equals(int(2), string("2.0"), true);
+        assert_equals(string("02"), string("2"), true);
+    }
+
+    #[test]
+    fn equals_compares_other_strings_as_text() {
+        assert_equals(int(1), string("abc"), false);
+        assert_equals(string("abc"), string("abc"), true);
+        assert_equals(Value::Float(1.5), string("x"), false);
+    }
+
+    #[test]
+    fn equals_agrees_with_compare() {
+        for (left, right) in [
+            (Value::Float(1.5), int(1)),
+            (int(2), string("10")),
+            (string("abc"), int(1)),
+            (string("b"), string("a")),
+        ] {
+            let equal = equals(left.clone(), right.clone()).unwrap();
+            let ordering = compare(left, right).unwrap();
+            assert_eq!(equal, ordering == Ordering::Equal);
+        }
+    }
+
+    #[test]
+    fn compare_is_antisymmetric() {
+        for (left, right) in [
+            (int(2), string("10")),
+            (Value::Float(1.5), int(1)),
+            (string("abc"), int(1)),
+            (string("a"), string("b")),
+        ] {
+            let forward = compare(left.clone(), right.clone()).unwrap();
+            let backward = compare(right, left).unwrap();
+            assert_eq!(forward, backward.reverse());
+        }
+    }
+
+    #[test]
+    fn compare_orders_numeric_strings_as_numbers() {
+        assert_eq!(compare(int(2), string("10")).ok(), Some(Ordering::Less));
+        assert_eq!(
+            compare(string("9"), string("10")).ok(),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn compare_rejects_nan() {
+        assert!(matches!(
+            compare(Value::Float(fvalue::NAN), int(1)),
+            Err(VmError::Unordered)
+        ));
+    }
+}
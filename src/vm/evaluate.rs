@@ -1,24 +1,278 @@
-use super::{runtime, value::Value, vm::Vm};
+use std::rc::Rc;
+
+use super::{
+    builtins::{describe, Builtin},
+    local::ScopeSearch,
+    redirect::Redirect,
+    runtime::{self, BuiltinStreams, PipelineOutput, Stage},
+    value::{ivalue, Value},
+    vm::{Vm, VmError},
+};
 
 pub enum EvaluateContext {
     None,
     Assignment,
+    /// The value is being tested for truth, so commands succeed or fail.
+    Condition,
+}
+
+/// The text a builtin's output is shown as. Anything other than text is shown
+/// on a line of its own.
+fn display(out: Value) -> String {
+    match out {
+        Value::None => String::new(),
+        Value::String(text) => text,
+        value => format!("{}\n", value),
+    }
 }
 
 impl Vm {
-    pub(super) fn evaluate(&self, v: Value, context: EvaluateContext) -> Value {
+    pub(super) fn evaluate(&mut self, v: Value, context: EvaluateContext) -> Value {
         match v {
-            Value::Command(cmd, args) => match context {
-                EvaluateContext::Assignment => {
-                    let (_, out, _) = runtime::execute(cmd, args, true);
-                    Value::String(out)
+            Value::Command(cmd, args, redirects) => {
+                let capture = matches!(context, EvaluateContext::Assignment);
+                let (status, out) = self.run_command(cmd.clone(), args, redirects, capture);
+                self.finish_command(&cmd, status, &context);
+
+                match context {
+                    EvaluateContext::Assignment => out,
+                    EvaluateContext::Condition => Value::Bool(status == 0),
+                    EvaluateContext::None => Value::Int(status),
                 }
-                _ => {
-                    let (status, _, _) = runtime::execute(cmd, args, false);
-                    Value::Int(status)
+            }
+            Value::Pipeline(stages) => {
+                let name = Value::Pipeline(stages.clone()).to_native_string();
+                let stages: Vec<Stage> = stages
+                    .into_iter()
+                    .filter_map(|stage| match stage {
+                        Value::Command(cmd, args, redirects) => Some((cmd, args, redirects)),
+                        _ => None,
+                    })
+                    .collect();
+
+                let capture = matches!(context, EvaluateContext::Assignment);
+                let (statuses, out) = if stages.iter().any(|(cmd, ..)| self.runs_in_shell(cmd)) {
+                    self.run_mixed_pipeline(stages, capture)
+                } else {
+                    let output = if capture {
+                        PipelineOutput::Capture
+                    } else {
+                        PipelineOutput::Inherit
+                    };
+                    self.run_pipeline(stages, None, output)
+                };
+                let status = statuses.last().copied().unwrap_or(0);
+                self.set_pipe_status(statuses);
+                self.finish_command(&name, status, &context);
+
+                match context {
+                    EvaluateContext::Assignment => Value::String(out),
+                    EvaluateContext::Condition => Value::Bool(status == 0),
+                    EvaluateContext::None => Value::Int(status),
                 }
-            },
+            }
             x => x,
         }
     }
+
+    /// Runs a function, builtin or external command, returning its status
+    /// and, if `capture` is set, its output.
+    fn run_command(
+        &mut self,
+        cmd: String,
+        args: Vec<Value>,
+        redirects: Vec<Redirect>,
+        capture: bool,
+    ) -> (ivalue, Value) {
+        // Functions come first, so that they can wrap a builtin or command of
+        // the same name.
+        if let Some(function) = self.function(&cmd) {
+            if redirects.is_empty() {
+                let (out, status) = self.call_as_command(&cmd, function, args, capture);
+                (status, if capture { out } else { Value::None })
+            } else {
+                eprintln!("{}: the output of a function can't be redirected", cmd);
+                (1, Value::None)
+            }
+        } else if let Some(builtin) = self.builtin(&cmd) {
+            self.run_builtin(&cmd, builtin, args, &redirects, capture)
+        } else {
+            let output = if capture {
+                PipelineOutput::Capture
+            } else {
+                PipelineOutput::Inherit
+            };
+            let (statuses, out) = self.run_pipeline(vec![(cmd, args, redirects)], None, output);
+            (statuses.last().copied().unwrap_or(127), Value::String(out))
+        }
+    }
+
+    /// Runs a builtin with its redirections applied. Its output is printed
+    /// unless it's redirected, or `capture` is set and it's returned instead.
+    fn run_builtin(
+        &mut self,
+        cmd: &str,
+        builtin: Rc<dyn Builtin>,
+        args: Vec<Value>,
+        redirects: &[Redirect],
+        capture: bool,
+    ) -> (ivalue, Value) {
+        let mut streams = match BuiltinStreams::open(redirects) {
+            Ok(streams) => streams,
+            Err(e) => {
+                eprintln!("{}", e);
+                return (1, Value::None);
+            }
+        };
+
+        let (out, status) = builtin.run(self, args);
+        if capture && !streams.redirects_output() {
+            return (status, out);
+        }
+
+        if let Err(e) = streams.write_output(&display(out)) {
+            eprintln!("{}: {}", cmd, describe(&e));
+        }
+        (status, Value::None)
+    }
+
+    /// Whether `cmd` is a function or builtin, run by the shell itself rather
+    /// than as a process of its own.
+    fn runs_in_shell(&self, cmd: &str) -> bool {
+        self.function(cmd).is_some() || self.is_builtin(cmd)
+    }
+
+    /// Runs a pipeline with functions or builtins in it. Each of those is run
+    /// by the shell in turn, with its output written to the stage after it,
+    /// and the commands between them are run as pipelines of their own. As
+    /// functions and builtins don't read their input, what's piped into one
+    /// is discarded. Returns the status of each stage and, if `capture` is
+    /// set, the output of the last.
+    fn run_mixed_pipeline(&mut self, stages: Vec<Stage>, capture: bool) -> (Vec<ivalue>, String) {
+        let mut statuses = Vec::new();
+        let mut out = None;
+        let mut stages = stages.into_iter().peekable();
+
+        while let Some((cmd, args, redirects)) = stages.next() {
+            if self.runs_in_shell(&cmd) {
+                let is_last = stages.peek().is_none();
+                let (status, output) = self.run_command(cmd, args, redirects, capture || !is_last);
+                statuses.push(status);
+                out = Some(display(output));
+                continue;
+            }
+
+            let mut commands = vec![(cmd, args, redirects)];
+            while let Some(stage) = stages.next_if(|(cmd, ..)| !self.runs_in_shell(cmd)) {
+                commands.push(stage);
+            }
+
+            let output = match (stages.peek().is_some(), capture) {
+                (true, _) => PipelineOutput::Discard,
+                (false, true) => PipelineOutput::Capture,
+                (false, false) => PipelineOutput::Inherit,
+            };
+            let (command_statuses, command_out) = self.run_pipeline(commands, out.take(), output);
+            statuses.extend(command_statuses);
+            out = Some(command_out);
+        }
+
+        (statuses, out.unwrap_or_default())
+    }
+
+    /// The function in `@name`, if there is one, so that `name` can be run
+    /// like a command.
+    pub(super) fn function(&self, name: &str) -> Option<(u8, usize, usize)> {
+        match self
+            .get_local(&name.to_owned(), ScopeSearch::AllScopes)?
+            .value
+        {
+            Value::Function(arity, chunk_id, offset) => Some((arity, chunk_id, offset)),
+            _ => None,
+        }
+    }
+
+    /// The names of the functions that can be run like commands.
+    pub fn function_names(&self) -> Vec<String> {
+        self.local_names()
+            .into_iter()
+            .filter(|name| self.function(name).is_some())
+            .collect()
+    }
+
+    /// Runs a function invoked like a command, e.g. `greet world`. Its
+    /// parameters are bound to the first arguments, and every argument is in
+    /// `@args`, `@1`, `@2`... with their number in `@argc`. Returns the
+    /// function's result and the status of the last command it ran.
+    fn call_as_command(
+        &mut self,
+        name: &str,
+        (arity, chunk_id, offset): (u8, usize, usize),
+        args: Vec<Value>,
+        capture: bool,
+    ) -> (Value, ivalue) {
+        let words = runtime::arguments(&args);
+        if words.len() < arity as usize {
+            let error = VmError::ArityMismatch(arity, words.len());
+            eprintln!("{}: {}", name, error);
+            return (Value::None, 2);
+        }
+
+        // Words that read as numbers are passed as numbers, so that e.g.
+        // `add 1 2` adds rather than joins.
+        let params = words[..arity as usize]
+            .iter()
+            .map(|word| Value::from_word(word))
+            .collect();
+        let context = if capture {
+            EvaluateContext::Assignment
+        } else {
+            EvaluateContext::None
+        };
+
+        self.begin_scope();
+        self.set_positional(self.scopes.len() - 1, words);
+        let result = self.run_function((chunk_id, offset), params, context);
+        let _ = self.end_scope();
+
+        match result {
+            Ok(value) => (value, self.last_status()),
+            // An error stops the caller too, as if it were raised there.
+            Err(e) => {
+                let status = e.exit_status();
+                self.halt(e);
+                (Value::None, status)
+            }
+        }
+    }
+
+    /// Records the exit status of a command in `@status` (also written `$?`),
+    /// and stops if it failed while `set -e` is on. Failures in conditions
+    /// are expected, so they never stop the shell.
+    pub(super) fn finish_command(&mut self, name: &str, status: ivalue, context: &EvaluateContext) {
+        self.define_global("status".to_owned(), Value::Int(status));
+
+        if self.options.errexit && status != 0 && !matches!(context, EvaluateContext::Condition) {
+            self.halt(VmError::CommandFailed(name.to_owned(), status));
+        }
+    }
+
+    /// The exit status of the last command run, or 0 if none has been.
+    pub fn last_status(&self) -> ivalue {
+        match self.get_local(&"status".to_owned(), ScopeSearch::AllScopes) {
+            Some(local) => local.value.to_ivalue().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// Records the exit status of each stage of the last pipeline in
+    /// `@pipestatus`, e.g. "0 1 0".
+    fn set_pipe_status(&mut self, statuses: Vec<ivalue>) {
+        let text = statuses
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        self.define_global("pipestatus".to_owned(), Value::String(text));
+    }
 }
@@ -0,0 +1,191 @@
+use std::{fs, path::Path};
+
+/// Expands a shell-style pattern using `*`, `?` and `[...]` into the paths
+/// that match it, in sorted order. As in other shells, hidden files are only
+/// matched by a component that starts with a dot.
+pub fn expand(pattern: &str) -> Vec<String> {
+    let mut paths = vec![if pattern.starts_with('/') {
+        "/".to_owned()
+    } else {
+        String::new()
+    }];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        if !component.contains(['*', '?', '[']) {
+            paths = paths.iter().map(|path| join(path, component)).collect();
+            continue;
+        }
+
+        let component: Vec<char> = component.chars().collect();
+        let mut found = Vec::new();
+
+        for path in &paths {
+            let dir = if path.is_empty() { "." } else { path };
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| !name.starts_with('.') || component[0] == '.')
+                .filter(|name| matches(&component, &name.chars().collect::<Vec<char>>()))
+                .collect();
+            names.sort();
+
+            found.extend(names.iter().map(|name| join(path, name)));
+        }
+
+        paths = found;
+    }
+
+    paths
+        .into_iter()
+        .filter(|path| !path.is_empty() && Path::new(path).symlink_metadata().is_ok())
+        .collect()
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() || path.ends_with('/') {
+        format!("{}{}", path, name)
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
+
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| matches(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some('[') => match (class_end(pattern), name.first()) {
+            (Some(end), Some(&c)) => {
+                in_class(&pattern[1..end], c) && matches(&pattern[end + 1..], &name[1..])
+            }
+            (Some(_), None) => false,
+            // An unclosed `[` is just a character.
+            (None, _) => name.first() == Some(&'[') && matches(&pattern[1..], &name[1..]),
+        },
+        Some(c) => name.first() == Some(c) && matches(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Finds the `]` closing the class that starts the pattern. A `]` straight
+/// after the opening `[` (or `[!`) is part of the class.
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let mut start = 1;
+    if matches!(pattern.get(start), Some('!' | '^')) {
+        start += 1;
+    }
+
+    pattern
+        .iter()
+        .skip(start + 1)
+        .position(|&c| c == ']')
+        .map(|i| i + start + 1)
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negate, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    found != negate
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn glob_matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        matches(&pattern, &name)
+    }
+
+    #[test]
+    fn star_matches_any_run_of_characters() {
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(glob_matches("*.rs", ".rs"));
+        assert!(glob_matches("a*b*c", "aXXbYc"));
+        assert!(!glob_matches("*.rs", "main.rsx"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_matches("?.md", "a.md"));
+        assert!(glob_matches("?.md", "é.md"));
+        assert!(!glob_matches("?.md", ".md"));
+        assert!(!glob_matches("?.md", "ab.md"));
+    }
+
+    #[test]
+    fn classes_match_listed_characters_and_ranges() {
+        assert!(glob_matches("[ab].txt", "a.txt"));
+        assert!(!glob_matches("[ab].txt", "c.txt"));
+        assert!(glob_matches("[a-c]", "b"));
+        assert!(!glob_matches("[a-c]", "d"));
+        assert!(glob_matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn negated_classes_match_other_characters() {
+        assert!(glob_matches("[!ab]", "c"));
+        assert!(glob_matches("[^ab]", "c"));
+        assert!(!glob_matches("[!ab]", "a"));
+    }
+
+    #[test]
+    fn bracket_straight_after_opening_is_in_the_class() {
+        assert!(glob_matches("[]a]", "]"));
+        assert!(glob_matches("[!]]", "a"));
+        assert!(!glob_matches("[!]]", "]"));
+    }
+
+    #[test]
+    fn unclosed_bracket_is_a_literal() {
+        assert!(glob_matches("[ab", "[ab"));
+        assert!(!glob_matches("[ab", "a"));
+    }
+
+    #[test]
+    fn expand_sorts_matches_and_skips_hidden_files() {
+        let dir = env::temp_dir().join(format!("insh-glob-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in ["b.txt", "a.txt", ".hidden.txt", "c.md", "sub/d.txt"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let root = dir.to_str().unwrap();
+
+        assert_eq!(
+            expand(&format!("{}/*.txt", root)),
+            [format!("{}/a.txt", root), format!("{}/b.txt", root)]
+        );
+        assert_eq!(
+            expand(&format!("{}/.*.txt", root)),
+            [format!("{}/.hidden.txt", root)]
+        );
+        assert_eq!(
+            expand(&format!("{}/*/d.txt", root)),
+            [format!("{}/sub/d.txt", root)]
+        );
+        assert!(expand(&format!("{}/*.zzz", root)).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
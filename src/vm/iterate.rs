@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+use super::{
+    evaluate::EvaluateContext,
+    value::{sorted_entries, Value},
+    vm::Vm,
+};
+
+/// The items left to visit in a `for` loop.
+pub(super) struct Iteration {
+    items: VecDeque<Value>,
+}
+
+impl Iteration {
+    pub(super) fn next(&mut self) -> Option<Value> {
+        match self.items.pop_front()? {
+            // Ranges are visited lazily, so that a large one doesn't have to
+            // be built up front.
+            Value::Range(from, to) => {
+                if from != to {
+                    let step = if from < to { 1 } else { -1 };
+                    self.items.push_front(Value::Range(from + step, to));
+                }
+                Some(Value::Int(from))
+            }
+            item => Some(item),
+        }
+    }
+}
+
+impl Vm {
+    /// Builds an iteration over each of the given values in turn. Commands are
+    /// run and, like strings, visited a line at a time; lists are visited an
+    /// item at a time, and maps a key at a time.
+    pub(super) fn iteration(&mut self, values: Vec<Value>) -> Iteration {
+        let mut items = VecDeque::new();
+
+        for value in values {
+            match self.evaluate(value, EvaluateContext::Assignment) {
+                Value::String(text) => {
+                    items.extend(text.lines().map(|line| Value::String(line.to_owned())))
+                }
+                Value::List(list) => items.extend(list),
+                Value::Map(map) => {
+                    items.extend(sorted_entries(&map).into_iter().map(|(k, _)| k.clone()))
+                }
+                Value::None => {}
+                value => items.push_back(value),
+            }
+        }
+
+        Iteration { items }
+    }
+}
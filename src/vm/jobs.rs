@@ -0,0 +1,458 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, IsTerminal},
+};
+
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{self, SigSet, SigmaskHow, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{self, Pid},
+};
+
+use super::{
+    evaluate::EvaluateContext,
+    op::Op,
+    runtime::{self, PipelineOutput, Stage},
+    signals,
+    value::{ivalue, Value},
+    vm::{Vm, VmError},
+};
+
+/// What a job, or one of the processes in it, is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    /// Finished, with this exit status.
+    Done(ivalue),
+}
+
+impl Display for JobState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => write!(f, "Running"),
+            JobState::Stopped => write!(f, "Stopped"),
+            JobState::Done(0) => write!(f, "Done"),
+            JobState::Done(status) => write!(f, "Exit {}", status),
+        }
+    }
+}
+
+/// A pipeline run in the background with `&`, or stopped while it was in the
+/// foreground.
+pub struct Job {
+    /// The number the job is referred to by, as `%n`.
+    pub id: usize,
+    /// The process group that every process in the job belongs to.
+    pub group: Pid,
+    pub command: String,
+    processes: Vec<(Pid, JobState)>,
+    /// The state the user was last told about, so that changes can be shown.
+    reported: JobState,
+}
+
+impl Job {
+    /// A job is stopped if any of its processes are, and done once all of them
+    /// are, with the status of the last.
+    pub fn state(&self) -> JobState {
+        if self
+            .processes
+            .iter()
+            .all(|(_, s)| matches!(s, JobState::Done(_)))
+        {
+            self.processes
+                .last()
+                .map_or(JobState::Done(0), |(_, state)| *state)
+        } else if self.processes.iter().any(|(_, s)| *s == JobState::Stopped) {
+            JobState::Stopped
+        } else {
+            JobState::Running
+        }
+    }
+
+    pub fn pids(&self) -> impl Iterator<Item = Pid> + '_ {
+        self.processes.iter().map(|(pid, _)| *pid)
+    }
+
+    fn record(&mut self, status: WaitStatus) {
+        let (pid, state) = match status {
+            WaitStatus::Exited(pid, code) => (pid, JobState::Done(code)),
+            WaitStatus::Signaled(pid, signal, _) => (pid, JobState::Done(128 + signal as i32)),
+            WaitStatus::Stopped(pid, _) => (pid, JobState::Stopped),
+            WaitStatus::Continued(pid) => (pid, JobState::Running),
+            _ => return,
+        };
+
+        if let Some(process) = self.processes.iter_mut().find(|(p, _)| *p == pid) {
+            process.1 = state;
+        }
+    }
+
+    /// Marks every process that hasn't finished as finished, for when they
+    /// can no longer be waited for.
+    fn abandon(&mut self) {
+        for (_, state) in &mut self.processes {
+            if !matches!(state, JobState::Done(_)) {
+                *state = JobState::Done(-1);
+            }
+        }
+    }
+
+    /// Checks on each process without blocking.
+    fn poll(&mut self) {
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+
+        for i in 0..self.processes.len() {
+            let (pid, state) = self.processes[i];
+            if matches!(state, JobState::Done(_)) {
+                continue;
+            }
+
+            match waitpid(pid, Some(flags)) {
+                Ok(status) => self.record(status),
+                Err(_) => self.processes[i].1 = JobState::Done(-1),
+            }
+        }
+    }
+
+    /// Blocks until the job has finished or been stopped or, if it's
+    /// `interruptible`, until the shell is sent a signal.
+    fn wait(&mut self, interruptible: bool) {
+        let group = Pid::from_raw(-self.group.as_raw());
+
+        while self.state() == JobState::Running {
+            match waitpid(group, Some(WaitPidFlag::WUNTRACED)) {
+                Ok(status) => self.record(status),
+                Err(Errno::EINTR) if interruptible && signals::any_pending() => return,
+                Err(Errno::EINTR) => {}
+                Err(_) => self.abandon(),
+            }
+        }
+    }
+
+    /// The status of each process, with those that haven't finished counted
+    /// as stopped.
+    fn statuses(&self) -> Vec<ivalue> {
+        self.processes
+            .iter()
+            .map(|(_, state)| match state {
+                JobState::Done(status) => *status,
+                _ => 128 + Signal::SIGTSTP as ivalue,
+            })
+            .collect()
+    }
+
+    /// Sends SIGCONT to every process, so that a stopped job runs again.
+    fn resume(&mut self) {
+        let _ = signal::killpg(self.group, Signal::SIGCONT);
+
+        for (_, state) in &mut self.processes {
+            if *state == JobState::Stopped {
+                *state = JobState::Running;
+            }
+        }
+    }
+}
+
+/// The text a job is shown as, e.g. `sleep 10 | cat`.
+fn describe(stages: &[Stage]) -> String {
+    stages
+        .iter()
+        .map(|(cmd, args, _)| {
+            let mut words = vec![cmd.clone()];
+            words.extend(runtime::arguments(args));
+            words.join(" ")
+        })
+        .collect::<Vec<String>>()
+        .join(" | ")
+}
+
+/// Waits for a job while it has the terminal, passing on the signals typed
+/// at it if the shell is sent them instead.
+fn wait_in_foreground(job: &mut Job) {
+    give_terminal_to(job.group);
+    signals::set_foreground(Some(job.group));
+    job.resume();
+    job.wait(false);
+    signals::set_foreground(None);
+    give_terminal_to(unistd::getpgrp());
+}
+
+/// Makes `group` the foreground process group of the terminal, if the shell
+/// has one, so that it's sent what's typed along with Ctrl-C and Ctrl-Z.
+fn give_terminal_to(group: Pid) {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return;
+    }
+
+    // When taking the terminal back the shell isn't in the foreground, and
+    // would be stopped by SIGTTOU if it weren't blocked.
+    let mut blocked = SigSet::empty();
+    blocked.add(Signal::SIGTTOU);
+    let mut previous = SigSet::empty();
+
+    let _ = signal::sigprocmask(SigmaskHow::SIG_BLOCK, Some(&blocked), Some(&mut previous));
+    let _ = unistd::tcsetpgrp(&stdin, group);
+    let _ = signal::sigprocmask(SigmaskHow::SIG_SETMASK, Some(&previous), None);
+}
+
+impl Vm {
+    /// Starts a command or pipeline without waiting for it, and adds it to
+    /// the job table.
+    pub(super) fn start_job(&mut self, value: Value) -> Result<(), VmError> {
+        let stages: Vec<Stage> = match value {
+            Value::Command(cmd, args, redirects) => vec![(cmd, args, redirects)],
+            Value::Pipeline(stages) => stages
+                .into_iter()
+                .filter_map(|stage| match stage {
+                    Value::Command(cmd, args, redirects) => Some((cmd, args, redirects)),
+                    _ => None,
+                })
+                .collect(),
+            x => return Err(VmError::InvalidOperand(Op::Background, x.type_name())),
+        };
+
+        let command = describe(&stages);
+
+        if let Some((cmd, ..)) = stages.iter().find(|(cmd, ..)| self.is_builtin(cmd)) {
+            eprintln!("{}: builtins can't be run in the background", cmd);
+            self.finish_command(&command, 1, &EvaluateContext::None);
+            return Ok(());
+        }
+        if let Some((cmd, ..)) = stages.iter().find(|(cmd, ..)| self.function(cmd).is_some()) {
+            eprintln!("{}: functions can't be run in the background", cmd);
+            self.finish_command(&command, 1, &EvaluateContext::None);
+            return Ok(());
+        }
+
+        let (job, failures) = self.spawn_job(stages, None);
+        let Some(job) = job else {
+            let status = failures.last().copied().flatten().unwrap_or(127);
+            self.finish_command(&command, status, &EvaluateContext::None);
+            return Ok(());
+        };
+
+        if self.options.monitor {
+            if let Some(pid) = job.pids().last() {
+                eprintln!("[{}] {}", job.id, pid);
+            }
+        }
+
+        self.jobs.push(job);
+        self.finish_command(&command, 0, &EvaluateContext::None);
+        Ok(())
+    }
+
+    /// Starts a pipeline in a process group of its own, returning it as a
+    /// job (not yet in the table) unless none of it could be started, along
+    /// with the status of each stage that couldn't be.
+    fn spawn_job(
+        &self,
+        stages: Vec<Stage>,
+        input: Option<String>,
+    ) -> (Option<Job>, Vec<Option<ivalue>>) {
+        let command = describe(&stages);
+        let children = runtime::spawn_job(stages, input);
+        let failures = children
+            .iter()
+            .map(|child| child.as_ref().err().copied())
+            .collect();
+        let children: Vec<_> = children.into_iter().flatten().collect();
+
+        let job = children.first().map(|leader| Job {
+            id: self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1,
+            group: Pid::from_raw(leader.id() as i32),
+            command,
+            processes: children
+                .iter()
+                .map(|child| (Pid::from_raw(child.id() as i32), JobState::Running))
+                .collect(),
+            reported: JobState::Running,
+        });
+        (job, failures)
+    }
+
+    /// Runs a pipeline and waits for it, returning the status of each stage
+    /// and the output of the last, if it's captured. `input` is written to
+    /// the first stage. With job control on, a pipeline whose output is shown
+    /// is given the terminal, and is kept as a job if stopped.
+    pub(super) fn run_pipeline(
+        &mut self,
+        stages: Vec<Stage>,
+        input: Option<String>,
+        output: PipelineOutput,
+    ) -> (Vec<ivalue>, String) {
+        let (statuses, out) = if output != PipelineOutput::Inherit || !self.options.monitor {
+            runtime::execute_pipeline(stages, input, output)
+        } else {
+            (self.run_in_foreground(stages, input), String::new())
+        };
+
+        // A command stopped by Ctrl-C stops the shell's code too, e.g. the
+        // loop it was run from.
+        if statuses.contains(&(128 + Signal::SIGINT as ivalue)) {
+            signals::interrupt();
+        }
+        (statuses, out)
+    }
+
+    fn run_in_foreground(&mut self, stages: Vec<Stage>, input: Option<String>) -> Vec<ivalue> {
+        let (job, failures) = self.spawn_job(stages, input);
+        let Some(mut job) = job else {
+            return failures.into_iter().map(|f| f.unwrap_or(-1)).collect();
+        };
+
+        wait_in_foreground(&mut job);
+
+        // The stages that were started take their statuses from the job, in
+        // order, between those that failed to start.
+        let mut started = job.statuses().into_iter();
+        let statuses = failures
+            .into_iter()
+            .map(|failure| failure.or_else(|| started.next()).unwrap_or(-1))
+            .collect();
+        if job.state() == JobState::Stopped {
+            job.reported = JobState::Stopped;
+            self.jobs.push(job);
+            eprintln!("\n{}", self.job_line(self.jobs.len() - 1));
+        }
+        statuses
+    }
+
+    /// Checks on every job without blocking.
+    pub fn update_jobs(&mut self) {
+        for job in &mut self.jobs {
+            job.poll();
+        }
+    }
+
+    /// Lines telling the user about jobs that have finished or stopped since
+    /// they were last told, e.g. `[1]+  Done    sleep 10`. Finished jobs are
+    /// then forgotten.
+    pub fn job_notifications(&mut self) -> Vec<String> {
+        self.update_jobs();
+
+        let mut lines = Vec::new();
+        for index in 0..self.jobs.len() {
+            let state = self.jobs[index].state();
+            if state != JobState::Running && state != self.jobs[index].reported {
+                lines.push(self.job_line(index));
+                self.jobs[index].reported = state;
+            }
+        }
+
+        self.forget_finished_jobs();
+        lines
+    }
+
+    pub(super) fn forget_finished_jobs(&mut self) {
+        self.jobs
+            .retain(|job| !matches!(job.state(), JobState::Done(_)));
+    }
+
+    /// The indices of the jobs, most current first. Stopped jobs come before
+    /// running ones, and later jobs before earlier ones.
+    fn jobs_by_currency(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.jobs.len()).collect();
+        order.sort_by_key(|&i| {
+            let job = &self.jobs[i];
+            std::cmp::Reverse((job.state() == JobState::Stopped, job.id))
+        });
+        order
+    }
+
+    /// How a job is listed by `jobs`. The current job, which `fg` and `bg`
+    /// use by default, is marked with `+` and the one before it with `-`.
+    pub(super) fn job_line(&self, index: usize) -> String {
+        let job = &self.jobs[index];
+        let order = self.jobs_by_currency();
+        let marker = match order.iter().position(|&i| i == index) {
+            Some(0) => '+',
+            Some(1) => '-',
+            _ => ' ',
+        };
+
+        let state = job.state();
+        let suffix = if state == JobState::Running { " &" } else { "" };
+        format!(
+            "[{}]{}  {:<24}{}{}",
+            job.id,
+            marker,
+            state.to_string(),
+            job.command,
+            suffix
+        )
+    }
+
+    /// Finds a job from a job spec, without its leading `%`: a number, `+` or
+    /// `%` for the current job, `-` for the previous one, or the start of the
+    /// job's command.
+    pub(super) fn find_job(&self, spec: &str) -> Option<usize> {
+        let order = self.jobs_by_currency();
+        match spec {
+            "" | "+" | "%" => order.first().copied(),
+            "-" => order.get(1).copied(),
+            spec => match spec.parse::<usize>() {
+                Ok(id) => self.jobs.iter().position(|job| job.id == id),
+                Err(_) => self
+                    .jobs
+                    .iter()
+                    .rposition(|job| job.command.starts_with(spec)),
+            },
+        }
+    }
+
+    /// The job that a process belongs to.
+    pub(super) fn find_job_by_pid(&self, pid: Pid) -> Option<usize> {
+        self.jobs
+            .iter()
+            .position(|job| job.pids().any(|p| p == pid))
+    }
+
+    /// Blocks until a job finishes or is stopped, or the shell is sent a
+    /// signal, returning the job's status. Finished jobs are removed from the
+    /// table.
+    pub(super) fn wait_for_job(&mut self, index: usize) -> ivalue {
+        let job = &mut self.jobs[index];
+        job.wait(true);
+
+        match job.state() {
+            JobState::Done(status) => {
+                self.jobs.remove(index);
+                status
+            }
+            JobState::Stopped => 128 + Signal::SIGTSTP as ivalue,
+            JobState::Running => 128 + signals::first_pending().map_or(0, |sig| sig as ivalue),
+        }
+    }
+
+    /// Continues a job in the foreground, giving it the terminal until it
+    /// finishes or is stopped again.
+    pub(super) fn foreground_job(&mut self, index: usize) -> ivalue {
+        let job = &mut self.jobs[index];
+        wait_in_foreground(job);
+
+        match job.state() {
+            JobState::Done(status) => {
+                self.jobs.remove(index);
+                status
+            }
+            state => {
+                job.reported = state;
+                eprintln!("\n{}", self.job_line(index));
+                128 + Signal::SIGTSTP as ivalue
+            }
+        }
+    }
+
+    /// Continues a stopped job in the background.
+    pub(super) fn background_job(&mut self, index: usize) {
+        let job = &mut self.jobs[index];
+        job.resume();
+        job.reported = JobState::Running;
+    }
+}
@@ -0,0 +1,331 @@
+use super::{
+    evaluate::EvaluateContext,
+    op::Op,
+    value::{ivalue, Value},
+    vm::{Vm, VmError},
+};
+
+/// Resolves a possibly negative index against a length, counting negative
+/// indices back from the end.
+fn resolve(index: ivalue, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        len as i64 + index as i64
+    } else {
+        index as i64
+    };
+
+    if (0..len as i64).contains(&index) {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+/// Resolves the bounds of a slice, where `None` means the start or end of the
+/// list. Out of range bounds are clamped, so a slice is never an error.
+fn resolve_bounds(from: Option<ivalue>, to: Option<ivalue>, len: usize) -> (usize, usize) {
+    let clamp = |bound: ivalue| -> usize {
+        let bound = if bound < 0 {
+            len as i64 + bound as i64
+        } else {
+            bound as i64
+        };
+        bound.clamp(0, len as i64) as usize
+    };
+
+    let from = from.map_or(0, clamp);
+    let to = to.map_or(len, clamp);
+    (from, to.max(from))
+}
+
+fn bound(value: Value) -> Result<Option<ivalue>, VmError> {
+    match value {
+        Value::None => Ok(None),
+        value => match value.to_ivalue() {
+            Ok(bound) => Ok(Some(bound)),
+            Err(VmError::InvalidConversion(..)) => Err(VmError::InvalidOperands(
+                Op::Index,
+                "list",
+                value.type_name(),
+            )),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+/// Converts the index of a list or string, naming the types if it can't be.
+fn index_of(container: &'static str, key: &Value) -> Result<ivalue, VmError> {
+    match key {
+        Value::Int(_) | Value::Float(_) | Value::Bool(_) | Value::String(_) => key.to_ivalue(),
+        _ => Err(VmError::InvalidOperands(
+            Op::Index,
+            container,
+            key.type_name(),
+        )),
+    }
+}
+
+impl Vm {
+    /// Pops `count` values and pushes them as a list. Commands are run, and
+    /// their output becomes the item.
+    pub(super) fn make_list(&mut self, count: ivalue) -> Result<(), VmError> {
+        let mut items = Vec::new();
+        for _ in 0..count {
+            let item = self.pop_stack()?;
+            items.push(self.evaluate(item, EvaluateContext::Assignment));
+        }
+        items.reverse();
+
+        self.push_stack(Value::List(items));
+        Ok(())
+    }
+
+    /// Pops an index and a list or string, and pushes the item at that index.
+    /// A map is indexed by key instead, giving none if the key is missing.
+    pub(super) fn index(&mut self) -> Result<(), VmError> {
+        let key = self.pop_stack()?;
+
+        let item = match self.pop_stack()? {
+            Value::Map(mut map) => map.remove(&key).unwrap_or(Value::None),
+            Value::List(mut items) => {
+                let index = index_of("list", &key)?;
+                let i = resolve(index, items.len())
+                    .ok_or(VmError::IndexOutOfRange(index, items.len()))?;
+                items.swap_remove(i)
+            }
+            Value::String(text) => {
+                let index = index_of("string", &key)?;
+                let chars: Vec<char> = text.chars().collect();
+                let i = resolve(index, chars.len())
+                    .ok_or(VmError::IndexOutOfRange(index, chars.len()))?;
+                Value::String(chars[i].to_string())
+            }
+            x => {
+                return Err(VmError::InvalidOperands(
+                    Op::Index,
+                    x.type_name(),
+                    key.type_name(),
+                ))
+            }
+        };
+
+        self.push_stack(item);
+        Ok(())
+    }
+
+    /// Pops the bounds of a slice and a list or string, and pushes the part
+    /// between them.
+    pub(super) fn slice(&mut self) -> Result<(), VmError> {
+        let to = bound(self.pop_stack()?)?;
+        let from = bound(self.pop_stack()?)?;
+
+        let part = match self.pop_stack()? {
+            Value::List(items) => {
+                let (from, to) = resolve_bounds(from, to, items.len());
+                Value::List(items[from..to].to_vec())
+            }
+            Value::String(text) => {
+                let chars: Vec<char> = text.chars().collect();
+                let (from, to) = resolve_bounds(from, to, chars.len());
+                Value::String(chars[from..to].iter().collect())
+            }
+            x => return Err(VmError::InvalidOperand(Op::Slice, x.type_name())),
+        };
+
+        self.push_stack(part);
+        Ok(())
+    }
+
+    /// Pops a value and pushes its length: the number of items in a list, or
+    /// characters in a string. A command's output is measured.
+    pub(super) fn length(&mut self) -> Result<(), VmError> {
+        let value = self.pop_stack()?;
+        let len = match self.evaluate(value, EvaluateContext::Assignment) {
+            Value::List(items) => items.len(),
+            Value::Map(map) => map.len(),
+            Value::String(text) => text.chars().count(),
+            Value::None => 0,
+            x => return Err(VmError::InvalidOperand(Op::Length, x.type_name())),
+        };
+
+        self.push_stack(Value::Int(len as ivalue));
+        Ok(())
+    }
+
+    /// Whether either operand of a binary operator is a list.
+    pub(super) fn has_list_operand(&self) -> bool {
+        self.stack
+            .iter()
+            .rev()
+            .take(2)
+            .any(|v| matches!(v, Value::List(_)))
+    }
+
+    /// Pops two values, at least one of which is a list, and pushes them
+    /// joined: two lists are concatenated, and any other value is appended
+    /// (or prepended) as a single item.
+    pub(super) fn concat(&mut self) -> Result<(), VmError> {
+        let right = self.pop_stack()?;
+        let left = self.pop_stack()?;
+
+        let items = match (left, right) {
+            (Value::List(mut left), Value::List(right)) => {
+                left.extend(right);
+                left
+            }
+            (Value::List(mut left), right) => {
+                left.push(right);
+                left
+            }
+            (left, Value::List(mut right)) => {
+                right.insert(0, left);
+                right
+            }
+            (left, right) => vec![left, right],
+        };
+
+        self.push_stack(Value::List(items));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[ivalue]) -> Value {
+        Value::List(items.iter().map(|&x| Value::Int(x)).collect())
+    }
+
+    fn ints(value: Value) -> Vec<ivalue> {
+        match value {
+            Value::List(items) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Int(x) => x,
+                    x => panic!("expected an int, found {}", x.type_name()),
+                })
+                .collect(),
+            x => panic!("expected a list, found {}", x.type_name()),
+        }
+    }
+
+    fn index(container: Value, key: Value) -> Result<Value, VmError> {
+        let mut vm = Vm::new();
+        vm.push_stack(container);
+        vm.push_stack(key);
+        vm.index()?;
+        vm.pop_stack()
+    }
+
+    fn slice(container: Value, from: Option<ivalue>, to: Option<ivalue>) -> Result<Value, VmError> {
+        let mut vm = Vm::new();
+        vm.push_stack(container);
+        vm.push_stack(from.map_or(Value::None, Value::Int));
+        vm.push_stack(to.map_or(Value::None, Value::Int));
+        vm.slice()?;
+        vm.pop_stack()
+    }
+
+    #[test]
+    fn resolve_counts_negative_indices_from_the_end() {
+        assert_eq!(resolve(0, 3), Some(0));
+        assert_eq!(resolve(2, 3), Some(2));
+        assert_eq!(resolve(-1, 3), Some(2));
+        assert_eq!(resolve(-3, 3), Some(0));
+    }
+
+    #[test]
+    fn resolve_rejects_out_of_range_indices() {
+        assert_eq!(resolve(3, 3), None);
+        assert_eq!(resolve(-4, 3), None);
+        assert_eq!(resolve(0, 0), None);
+        assert_eq!(resolve(-1, 0), None);
+        assert_eq!(resolve(ivalue::MIN, 3), None);
+    }
+
+    #[test]
+    fn resolve_bounds_defaults_to_the_whole_list() {
+        assert_eq!(resolve_bounds(None, None, 4), (0, 4));
+        assert_eq!(resolve_bounds(Some(1), None, 4), (1, 4));
+        assert_eq!(resolve_bounds(None, Some(-1), 4), (0, 3));
+    }
+
+    #[test]
+    fn resolve_bounds_clamps_out_of_range_bounds() {
+        assert_eq!(resolve_bounds(Some(-10), Some(10), 4), (0, 4));
+        assert_eq!(resolve_bounds(Some(5), None, 4), (4, 4));
+        assert_eq!(
+            resolve_bounds(Some(ivalue::MIN), Some(ivalue::MAX), 4),
+            (0, 4)
+        );
+    }
+
+    #[test]
+    fn resolve_bounds_empties_reversed_slices() {
+        assert_eq!(resolve_bounds(Some(3), Some(1), 4), (3, 3));
+        assert_eq!(resolve_bounds(Some(-1), Some(-3), 4), (3, 3));
+    }
+
+    #[test]
+    fn index_reads_from_either_end() {
+        assert!(matches!(
+            index(list(&[1, 2, 3]), Value::Int(0)),
+            Ok(Value::Int(1))
+        ));
+        assert!(matches!(
+            index(list(&[1, 2, 3]), Value::Int(-1)),
+            Ok(Value::Int(3))
+        ));
+        assert!(matches!(
+            index(Value::String("héllo".to_owned()), Value::Int(-4)),
+            Ok(Value::String(c)) if c == "é"
+        ));
+    }
+
+    #[test]
+    fn index_out_of_range_is_an_error() {
+        assert!(matches!(
+            index(list(&[1, 2, 3]), Value::Int(3)),
+            Err(VmError::IndexOutOfRange(3, 3))
+        ));
+        assert!(matches!(
+            index(list(&[1, 2, 3]), Value::Int(-4)),
+            Err(VmError::IndexOutOfRange(-4, 3))
+        ));
+        assert!(matches!(
+            index(Value::String(String::new()), Value::Int(0)),
+            Err(VmError::IndexOutOfRange(0, 0))
+        ));
+    }
+
+    #[test]
+    fn slice_takes_the_part_between_bounds() {
+        assert_eq!(
+            ints(slice(list(&[1, 2, 3, 4]), Some(1), Some(3)).unwrap()),
+            [2, 3]
+        );
+        assert_eq!(
+            ints(slice(list(&[1, 2, 3, 4]), Some(-2), None).unwrap()),
+            [3, 4]
+        );
+        assert_eq!(
+            ints(slice(list(&[1, 2, 3, 4]), None, Some(-3)).unwrap()),
+            [1]
+        );
+        assert!(matches!(
+            slice(Value::String("héllo".to_owned()), Some(1), Some(3)),
+            Ok(Value::String(s)) if s == "él"
+        ));
+    }
+
+    #[test]
+    fn slice_out_of_range_or_reversed_is_clamped() {
+        assert_eq!(
+            ints(slice(list(&[1, 2, 3]), Some(-10), Some(10)).unwrap()),
+            [1, 2, 3]
+        );
+        assert_eq!(ints(slice(list(&[1, 2, 3]), Some(5), None).unwrap()), []);
+        assert_eq!(ints(slice(list(&[1, 2, 3]), Some(2), Some(1)).unwrap()), []);
+    }
+}
@@ -39,6 +39,13 @@ impl Scope {
     pub fn get_local_mut(&mut self, name: &String) -> Option<&mut Local> {
         self.locals.get_mut(name)
     }
+
+    /// The locals defined in the scope, sorted by name.
+    pub fn locals(&self) -> Vec<(&String, &Local)> {
+        let mut locals: Vec<(&String, &Local)> = self.locals.iter().collect();
+        locals.sort_by_key(|(name, _)| *name);
+        locals
+    }
 }
 
 impl Vm {
@@ -46,23 +53,25 @@ impl Vm {
         self.scopes.push(Scope::new());
     }
 
-    pub fn end_scope(&mut self) {
-        if self.scopes.is_empty() {
-            panic!("scope stack underflow");
+    /// Leaves the innermost scope. The global scope is never left.
+    pub fn end_scope(&mut self) -> Result<(), VmError> {
+        if self.scopes.len() <= 1 {
+            return Err(VmError::ScopeUnderflow);
         }
         self.scopes.pop();
+        Ok(())
     }
 
     pub fn set_local(&mut self, name: &String, value: Value) -> Result<(), VmError> {
         if let Some(local) = self.get_local_mut(name, ScopeSearch::AllScopes) {
             if local.pinned {
-                Err(VmError::PinnedLocal)
+                Err(VmError::PinnedLocal(name.to_owned()))
             } else {
                 local.set(value);
                 Ok(())
             }
         } else {
-            Err(VmError::UndefinedLocal)
+            Err(VmError::UndefinedLocal(name.to_owned()))
         }
     }
 
@@ -84,6 +93,18 @@ impl Vm {
         }
     }
 
+    /// The names of the locals in every scope, without the `@`.
+    pub fn local_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.locals.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
     pub fn get_local(&self, name: &String, search: ScopeSearch) -> Option<&Local> {
         match search {
             ScopeSearch::CurrentOnly => {
@@ -108,14 +129,17 @@ impl Vm {
         value: Value,
         pinned: bool,
     ) -> Result<(), VmError> {
-        let last_scope = self.scopes.last_mut().ok_or(VmError::InvalidOperation)?;
+        let last_scope = self
+            .scopes
+            .last_mut()
+            .ok_or(VmError::InvalidOperation("no scope to define in"))?;
         let existing_local = last_scope.get_local_mut(&name);
 
         match existing_local {
             Some(Local {
                 value: _,
                 pinned: true,
-            }) => Err(VmError::PinnedLocal),
+            }) => Err(VmError::PinnedLocal(name)),
             Some(local) => {
                 local.pinned = pinned;
                 local.value = value;
@@ -127,4 +151,35 @@ impl Vm {
             }
         }
     }
+
+    /// Defines (or overwrites) a local in the outermost scope, regardless of
+    /// whether it has been pinned. Used for values maintained by the shell.
+    pub(super) fn define_global(&mut self, name: String, value: Value) {
+        self.define_in_scope(0, name, value);
+    }
+
+    /// Defines (or overwrites) a local in the scope at `depth`, counting from
+    /// the outermost, regardless of whether it has been pinned.
+    pub(super) fn define_in_scope(&mut self, depth: usize, name: String, value: Value) {
+        if let Some(scope) = self.scopes.get_mut(depth) {
+            scope.locals.insert(
+                name,
+                Local {
+                    value,
+                    pinned: false,
+                },
+            );
+        }
+    }
+
+    pub(super) fn remove_from_scope(&mut self, depth: usize, name: &str) -> Option<Local> {
+        self.scopes.get_mut(depth)?.locals.remove(name)
+    }
+
+    /// The depth of the innermost scope that defines `name`.
+    pub(super) fn scope_defining(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.locals.contains_key(name))
+    }
 }
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use super::{
+    evaluate::EvaluateContext,
+    op::Op,
+    value::{ivalue, Value},
+    vm::{Vm, VmError},
+};
+
+impl Vm {
+    /// Pops `count` key/value pairs and pushes them as a map. A later entry
+    /// replaces an earlier one with the same key.
+    pub(super) fn make_map(&mut self, count: ivalue) -> Result<(), VmError> {
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let value = self.pop_stack()?;
+            let key = self.pop_stack()?;
+            entries.push((key, value));
+        }
+
+        let mut map = HashMap::new();
+        for (key, value) in entries.into_iter().rev() {
+            let key = self.evaluate(key, EvaluateContext::Assignment);
+            let value = self.evaluate(value, EvaluateContext::Assignment);
+            map.insert(key, value);
+        }
+
+        self.push_stack(Value::Map(map));
+        Ok(())
+    }
+
+    /// Pops a container and a value, and pushes whether the value is a key of
+    /// the map, an item of the list, or part of the string.
+    pub(super) fn contains(&mut self) -> Result<(), VmError> {
+        let container = self.pop_stack()?;
+        let value = self.pop_stack()?;
+        let value = self.evaluate(value, EvaluateContext::Assignment);
+
+        let found = match self.evaluate(container, EvaluateContext::Assignment) {
+            Value::Map(map) => map.contains_key(&value),
+            Value::List(items) => items.contains(&value),
+            Value::String(text) => text.contains(&value.to_native_string()),
+            Value::Range(from, to) => match value.to_ivalue() {
+                Ok(x) => from.min(to) <= x && x <= from.max(to),
+                Err(_) => false,
+            },
+            x => {
+                return Err(VmError::InvalidOperands(
+                    Op::Contains,
+                    value.type_name(),
+                    x.type_name(),
+                ))
+            }
+        };
+
+        self.push_stack(Value::Bool(found));
+        Ok(())
+    }
+}
@@ -29,15 +29,37 @@ pub enum Op {
     Pipe = 53,
     Swap = 54,
     Equal = 55,
+    NotEqual = 56,
+    Less = 57,
+    LessEqual = 58,
+    Greater = 59,
+    GreaterEqual = 60,
+    Compare = 61,
+    Not = 62,
+    Range = 63,
 
     Command = 64,
+    Redirect = 65,
+    Glob = 66,
+    Background = 67,
+
+    List = 80,
+    Index = 81,
+    Slice = 82,
+    Length = 83,
+    Map = 84,
+    Contains = 85,
 
     Branch = 96,
     BranchIfFalse = 97,
     BranchBack = 98,
+    Iterate = 99,
+    IterateNext = 100,
+    IterateEnd = 101,
 
     SysCall = 128,
     FunctionDefinition = 129,
+    Call = 130,
 
     BeginScope = 224,
     EndScope = 225,
@@ -47,3 +69,87 @@ pub enum Op {
 }
 
 pub const OP_SIZE: usize = size_of::<Op>();
+
+impl TryFrom<u8> for Op {
+    type Error = u8;
+
+    /// Decodes an op from its byte. `Invalid` and `Unknown` only mark the
+    /// ends of the range, so they aren't ops that can be run.
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        let op = match byte {
+            1 => Op::Return,
+            8 => Op::IntConstant,
+            9 => Op::FloatConstant,
+            10 => Op::StringConstant,
+            11 => Op::BoolConstant,
+            12 => Op::NoneConstant,
+            16 => Op::Pop,
+            24 => Op::GetEnv,
+            25 => Op::SetEnv,
+            32 => Op::DefineLocal,
+            33 => Op::PinLocal,
+            34 => Op::GetLocal,
+            35 => Op::SetLocal,
+            48 => Op::Negate,
+            49 => Op::Add,
+            50 => Op::Subtract,
+            51 => Op::Multiply,
+            52 => Op::Divide,
+            53 => Op::Pipe,
+            54 => Op::Swap,
+            55 => Op::Equal,
+            56 => Op::NotEqual,
+            57 => Op::Less,
+            58 => Op::LessEqual,
+            59 => Op::Greater,
+            60 => Op::GreaterEqual,
+            61 => Op::Compare,
+            62 => Op::Not,
+            63 => Op::Range,
+            64 => Op::Command,
+            65 => Op::Redirect,
+            66 => Op::Glob,
+            67 => Op::Background,
+            80 => Op::List,
+            81 => Op::Index,
+            82 => Op::Slice,
+            83 => Op::Length,
+            84 => Op::Map,
+            85 => Op::Contains,
+            96 => Op::Branch,
+            97 => Op::BranchIfFalse,
+            98 => Op::BranchBack,
+            99 => Op::Iterate,
+            100 => Op::IterateNext,
+            101 => Op::IterateEnd,
+            128 => Op::SysCall,
+            129 => Op::FunctionDefinition,
+            130 => Op::Call,
+            224 => Op::BeginScope,
+            225 => Op::EndScope,
+            _ => return Err(byte),
+        };
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_from_decodes_each_op_to_its_own_byte() {
+        let ops: Vec<Op> = (0..=u8::MAX).filter_map(|b| Op::try_from(b).ok()).collect();
+        for op in ops {
+            assert_eq!(Op::try_from(op as u8), Ok(op));
+        }
+    }
+
+    #[test]
+    fn try_from_rejects_markers_and_gaps() {
+        assert_eq!(Op::try_from(Op::Invalid as u8), Err(0));
+        assert_eq!(Op::try_from(Op::Unknown as u8), Err(255));
+        assert_eq!(Op::try_from(2), Err(2));
+        assert_eq!(Op::try_from(54), Ok(Op::Swap));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{compile::compiler::Compiler, scan::scanner::Scanner};
+
+use super::{chunk::bytecode_chunk::ByteCodeChunk, vm::Vm};
+
+/// Shell options, changed with the `set` builtin.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Stop as soon as a command fails, as with `set -e` in other shells.
+    pub errexit: bool,
+    /// Job control: report background jobs as they start and finish, as with
+    /// `set -m`. This is on in an interactive shell.
+    pub monitor: bool,
+    /// Don't exit an interactive shell on Ctrl-D, only on `exit`.
+    pub ignoreeof: bool,
+    /// Print each token as the scanner reads it.
+    pub trace_tokens: bool,
+    /// Print the bytecode of everything compiled.
+    pub dump_bytecode: bool,
+    /// Print each op as it's run, along with the stack and scopes.
+    pub trace_exec: bool,
+}
+
+impl Options {
+    /// Turns the named option on or off, returning false if there's no such
+    /// option. Options can be given by name or by their single letter flag.
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        match name {
+            "errexit" | "e" => self.errexit = on,
+            "monitor" | "m" => self.monitor = on,
+            "ignoreeof" => self.ignoreeof = on,
+            "trace-tokens" => self.trace_tokens = on,
+            "dump-bytecode" => self.dump_bytecode = on,
+            "trace-exec" => self.trace_exec = on,
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Vm {
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// A compiler for `source` that prints what it reads and compiles if the
+    /// `trace-tokens` and `dump-bytecode` options are on.
+    pub fn compiler(&self, source: String) -> Compiler {
+        let mut compiler = Compiler::new(Scanner::new(source), ByteCodeChunk::new());
+        compiler.set_trace_tokens(self.options.trace_tokens);
+        compiler.set_dump_bytecode(self.options.dump_bytecode);
+        compiler
+    }
+
+    /// Turns a shell option on or off, as `set` does.
+    pub fn set_option(&mut self, name: &str, on: bool) -> bool {
+        self.options.set(name, on)
+    }
+}
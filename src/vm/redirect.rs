@@ -0,0 +1,22 @@
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RedirectKind {
+    /// `> file`
+    Output = 0,
+    /// `>> file`
+    Append = 1,
+    /// `< file`
+    Input = 2,
+    /// `2> file`
+    Error = 3,
+    /// `2>&1`
+    ErrorToOutput = 4,
+    /// `&> file`
+    All = 5,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Redirect {
+    pub kind: RedirectKind,
+    pub target: String,
+}
@@ -1,31 +1,329 @@
-use super::value::Value;
+use super::{
+    builtins::describe,
+    redirect::{Redirect, RedirectKind},
+    value::Value,
+};
 use crate::vm::value::ivalue;
-use std::process::Command;
+use nix::unistd;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, PipeReader, Read, Write},
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+        unix::process::{CommandExt, ExitStatusExt},
+    },
+    process::{Child, Command},
+    thread,
+};
 
-pub fn execute(cmd: String, args: Vec<Value>, capture: bool) -> (ivalue, String, String) {
-    let mut command = Command::new(cmd);
+pub type Stage = (String, Vec<Value>, Vec<Redirect>);
+
+/// Where the output of the last stage of a pipeline goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineOutput {
+    /// The shell's own stdout.
+    Inherit,
+    /// Read back by the shell, e.g. for `let @x = (ls)`.
+    Capture,
+    /// Nowhere: the stage writes to a pipe that nothing reads, as when it's
+    /// piped into a builtin.
+    Discard,
+}
+
+/// The words passed to a command for its arguments. A list is splatted into
+/// an argument per item.
+pub fn arguments(args: &[Value]) -> Vec<String> {
+    let mut words = Vec::new();
 
     for arg in args {
-        command.arg(arg.to_native_string());
+        match arg {
+            Value::List(items) => words.extend(items.iter().map(|item| item.to_native_string())),
+            arg => words.push(arg.to_native_string()),
+        }
+    }
+
+    words
+}
+
+fn build_command(cmd: String, args: Vec<Value>) -> Command {
+    let mut command = Command::new(cmd);
+    command.args(arguments(&args));
+    command
+}
+
+fn open_error(target: &str, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", target, describe(&e)))
+}
+
+/// Opens the target of an output redirection, e.g. `> file` or `>> file`.
+fn open_output(target: &str, append: bool) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(target)
+        .map_err(|e| open_error(target, e))
+}
+
+/// Applies redirections in order, so that e.g. `> out 2>&1` sends both
+/// streams to `out`. `stdout` is where output goes if not redirected: a pipe
+/// to the next stage, or `None` to inherit the shell's stdout.
+fn apply_redirects(
+    command: &mut Command,
+    redirects: &[Redirect],
+    mut stdout: Option<OwnedFd>,
+) -> io::Result<()> {
+    if let Some(fd) = &stdout {
+        command.stdout(fd.try_clone()?);
     }
 
-    if capture {
-        match command.output() {
-            Ok(out) => (
-                out.status.code().unwrap_or(0) as ivalue,
-                String::from_utf8_lossy(&out.stdout).to_string(),
-                String::from_utf8_lossy(&out.stderr).to_string(),
-            ),
-            Err(_) => (-1, String::new(), String::new()),
+    for Redirect { kind, target } in redirects {
+        let open = |append: bool| open_output(target, append).map(OwnedFd::from);
+
+        match kind {
+            RedirectKind::Output | RedirectKind::Append => {
+                let fd = open(*kind == RedirectKind::Append)?;
+                command.stdout(fd.try_clone()?);
+                stdout = Some(fd);
+            }
+            RedirectKind::All => {
+                let fd = open(false)?;
+                command.stdout(fd.try_clone()?);
+                command.stderr(fd.try_clone()?);
+                stdout = Some(fd);
+            }
+            RedirectKind::Input => {
+                command.stdin(File::open(target).map_err(|e| open_error(target, e))?);
+            }
+            RedirectKind::Error => {
+                command.stderr(open(false)?);
+            }
+            RedirectKind::ErrorToOutput => {
+                let fd = match &stdout {
+                    Some(fd) => fd.try_clone()?,
+                    None => io::stdout().as_fd().try_clone_to_owned()?,
+                };
+                command.stderr(fd);
+            }
         }
-    } else {
-        match command.status() {
-            Ok(s) => (
-                s.code().unwrap_or(0) as ivalue,
-                String::new(),
-                String::new(),
-            ),
-            Err(_) => (-1, String::new(), String::new()),
+    }
+
+    Ok(())
+}
+
+/// Where a builtin's output and errors go, as set by its redirections. While
+/// this is held, the shell's own stderr is sent wherever the builtin's is, and
+/// it's put back when this is dropped.
+pub struct BuiltinStreams {
+    stdout: Option<File>,
+    saved_stderr: Option<OwnedFd>,
+}
+
+impl BuiltinStreams {
+    /// Opens the targets of a builtin's redirections in order, as for a
+    /// command, so that e.g. `> out 2>&1` sends both streams to `out`.
+    pub fn open(redirects: &[Redirect]) -> io::Result<Self> {
+        let mut stdout: Option<File> = None;
+        let mut stderr: Option<File> = None;
+
+        for Redirect { kind, target } in redirects {
+            match kind {
+                RedirectKind::Output | RedirectKind::Append => {
+                    stdout = Some(open_output(target, *kind == RedirectKind::Append)?);
+                }
+                RedirectKind::All => {
+                    let file = open_output(target, false)?;
+                    stderr = Some(file.try_clone()?);
+                    stdout = Some(file);
+                }
+                RedirectKind::Error => stderr = Some(open_output(target, false)?),
+                RedirectKind::ErrorToOutput => {
+                    stderr = Some(match &stdout {
+                        Some(file) => file.try_clone()?,
+                        None => File::from(io::stdout().as_fd().try_clone_to_owned()?),
+                    });
+                }
+                // Builtins don't read their input.
+                RedirectKind::Input => {}
+            }
+        }
+
+        let saved_stderr = match stderr {
+            Some(file) => {
+                let stderr = io::stderr();
+                let saved = unistd::dup(stderr.as_raw_fd())?;
+                unistd::dup2(file.as_raw_fd(), stderr.as_raw_fd())?;
+                // SAFETY: `dup` just returned this descriptor, and nothing
+                // else owns it.
+                Some(unsafe { OwnedFd::from_raw_fd(saved) })
+            }
+            None => None,
+        };
+
+        Ok(BuiltinStreams {
+            stdout,
+            saved_stderr,
+        })
+    }
+
+    /// Whether the builtin's output is redirected away from where it would
+    /// otherwise go.
+    pub fn redirects_output(&self) -> bool {
+        self.stdout.is_some()
+    }
+
+    /// Writes the output of the builtin to wherever its redirections send
+    /// stdout, or else to the shell's own stdout.
+    pub fn write_output(&mut self, out: &str) -> io::Result<()> {
+        match &mut self.stdout {
+            Some(file) => file.write_all(out.as_bytes()),
+            None => {
+                let mut stdout = io::stdout();
+                stdout.write_all(out.as_bytes())?;
+                stdout.flush()
+            }
+        }
+    }
+}
+
+impl Drop for BuiltinStreams {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved_stderr {
+            let _ = unistd::dup2(saved.as_raw_fd(), io::stderr().as_raw_fd());
         }
     }
 }
+
+/// The processes started for a pipeline, and the read end of the pipe
+/// capturing the output of the last one. A stage that couldn't be started has
+/// the status it failed with instead.
+struct Spawned {
+    children: Vec<Result<Child, ivalue>>,
+    captured_stdout: Option<PipeReader>,
+}
+
+/// Starts every stage of a pipeline, with `input`, if given, written to the
+/// stdin of the first. If `job` is set, the stages are put in a process group
+/// of their own, led by the first, so that they can be stopped, continued and
+/// given the terminal together.
+fn spawn(stages: Vec<Stage>, input: Option<String>, output: PipelineOutput, job: bool) -> Spawned {
+    let count = stages.len();
+    let mut children: Vec<Result<Child, ivalue>> = Vec::with_capacity(count);
+    let mut next_stdin = None;
+    let mut input_writer = None;
+
+    if let Some(input) = input {
+        if let Ok((reader, writer)) = io::pipe() {
+            next_stdin = Some(reader);
+            input_writer = Some((writer, input));
+        }
+    }
+
+    let mut captured_stdout = None;
+    let mut group = 0;
+
+    for (i, (cmd, args, redirects)) in stages.into_iter().enumerate() {
+        let is_last = i == count - 1;
+        let mut command = build_command(cmd.clone(), args);
+
+        if job {
+            command.process_group(group);
+        }
+
+        if let Some(stdin) = next_stdin.take() {
+            command.stdin(stdin);
+        }
+
+        let mut stdout = None;
+        if !is_last || output != PipelineOutput::Inherit {
+            if let Ok((reader, writer)) = io::pipe() {
+                match (is_last, output) {
+                    (false, _) => next_stdin = Some(reader),
+                    (true, PipelineOutput::Capture) => captured_stdout = Some(reader),
+                    // The read end is closed here, so that the stage is
+                    // stopped by SIGPIPE if it writes.
+                    _ => {}
+                }
+                stdout = Some(OwnedFd::from(writer));
+            }
+        }
+
+        if let Err(e) = apply_redirects(&mut command, &redirects, stdout) {
+            eprintln!("{}", e);
+            children.push(Err(1));
+            continue;
+        }
+
+        // `command` is dropped at the end of each iteration, closing our copy
+        // of the write end of its pipe so the next stage sees end-of-file.
+        let child = command.spawn().map_err(|e| spawn_error(&cmd, e));
+        if let (0, Ok(child)) = (group, &child) {
+            group = child.id() as i32;
+        }
+        children.push(child);
+    }
+
+    // Written once the first stage has started, on another thread so that a
+    // stage that doesn't read all of it can't block the shell.
+    if let Some((mut writer, input)) = input_writer {
+        thread::spawn(move || {
+            let _ = writer.write_all(input.as_bytes());
+        });
+    }
+
+    Spawned {
+        children,
+        captured_stdout,
+    }
+}
+
+/// Reports why `cmd` couldn't be started, returning the status it fails
+/// with: 127 if there's no such command, or else 126, as in other shells.
+fn spawn_error(cmd: &str, e: io::Error) -> ivalue {
+    if e.kind() == io::ErrorKind::NotFound {
+        eprintln!("{}: command not found", cmd);
+        127
+    } else {
+        eprintln!("{}: {}", cmd, describe(&e));
+        126
+    }
+}
+
+/// Starts a pipeline without waiting for it, in a process group of its own.
+/// Returns each stage's process, or the status it failed to start with.
+pub fn spawn_job(stages: Vec<Stage>, input: Option<String>) -> Vec<Result<Child, ivalue>> {
+    spawn(stages, input, PipelineOutput::Inherit, true).children
+}
+
+/// Runs each stage with its stdout connected to the stdin of the next one.
+/// Returns the exit status of every stage (in order), along with the output
+/// of the final stage if it's captured.
+pub fn execute_pipeline(
+    stages: Vec<Stage>,
+    input: Option<String>,
+    output: PipelineOutput,
+) -> (Vec<ivalue>, String) {
+    let Spawned {
+        children,
+        captured_stdout,
+    } = spawn(stages, input, output, false);
+
+    let mut out = Vec::new();
+    if let Some(mut reader) = captured_stdout {
+        let _ = reader.read_to_end(&mut out);
+    }
+
+    let statuses = children
+        .into_iter()
+        .map(|child| match child.map(|mut c| c.wait()) {
+            // A process killed by a signal has a status of 128 plus the
+            // signal's number, as in other shells.
+            Ok(Ok(s)) => s.code().or(s.signal().map(|sig| 128 + sig)).unwrap_or(0),
+            Ok(Err(_)) => -1,
+            Err(status) => status,
+        })
+        .collect();
+
+    (statuses, String::from_utf8_lossy(&out).to_string())
+}
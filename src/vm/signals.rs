@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+
+use nix::{
+    libc::c_int,
+    sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    unistd::Pid,
+};
+
+use super::{
+    value::ivalue,
+    vm::{Vm, VmError},
+};
+
+/// Signals that have arrived but not been handled yet, a bit per signal.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// The process group of the job running in the foreground, or 0 if there
+/// isn't one with a group of its own.
+static FOREGROUND: AtomicI32 = AtomicI32::new(0);
+
+/// Whether the shell is interactive, and so catches the signals typed at the
+/// terminal rather than being stopped or killed by them.
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
+/// Signals typed at the terminal, which belong to the job in the foreground.
+const TERMINAL_SIGNALS: [Signal; 3] = [Signal::SIGINT, Signal::SIGTSTP, Signal::SIGQUIT];
+
+extern "C" fn on_signal(number: c_int) {
+    let Ok(sig) = Signal::try_from(number) else {
+        return;
+    };
+
+    // The job gets the signal instead of the shell. If it dies of SIGINT the
+    // shell is interrupted too, once it sees how the job finished.
+    let group = FOREGROUND.load(Ordering::Relaxed);
+    if group > 0 && TERMINAL_SIGNALS.contains(&sig) {
+        let _ = signal::killpg(Pid::from_raw(group), sig);
+        return;
+    }
+
+    PENDING.fetch_or(1 << number, Ordering::Relaxed);
+}
+
+fn set_handler(sig: Signal, handler: SigHandler) -> nix::Result<()> {
+    // Without SA_RESTART, a signal interrupts `wait` rather than waiting on.
+    let action = SigAction::new(handler, SaFlags::empty(), SigSet::empty());
+    // SAFETY: `on_signal` only touches atomics and calls killpg, both of
+    // which are safe to use in a signal handler.
+    unsafe { signal::sigaction(sig, &action) }.map(|_| ())
+}
+
+/// Catches SIGINT, so that Ctrl-C interrupts whatever the shell is running.
+/// With `job_control`, SIGTSTP and SIGQUIT are caught too, so that they only
+/// reach the job in the foreground.
+pub fn install(job_control: bool) {
+    JOB_CONTROL.store(job_control, Ordering::Relaxed);
+
+    for sig in TERMINAL_SIGNALS {
+        if sig == Signal::SIGINT || job_control {
+            let _ = set_handler(sig, SigHandler::Handler(on_signal));
+        }
+    }
+}
+
+/// Catches a signal so that a trap can be run for it.
+pub fn catch(sig: Signal) -> nix::Result<()> {
+    set_handler(sig, SigHandler::Handler(on_signal))
+}
+
+/// Stops catching a signal once its trap is removed, unless the shell
+/// catches it anyway.
+pub fn restore(sig: Signal) {
+    let shell_catches = sig == Signal::SIGINT
+        || (JOB_CONTROL.load(Ordering::Relaxed) && TERMINAL_SIGNALS.contains(&sig));
+
+    if !shell_catches {
+        let _ = set_handler(sig, SigHandler::SigDfl);
+    }
+}
+
+/// Sets the process group that signals typed at the terminal are passed on
+/// to, or `None` once the job in the foreground is done with.
+pub fn set_foreground(group: Option<Pid>) {
+    FOREGROUND.store(group.map_or(0, Pid::as_raw), Ordering::Relaxed);
+}
+
+pub fn any_pending() -> bool {
+    PENDING.load(Ordering::Relaxed) != 0
+}
+
+/// The lowest numbered signal that has arrived but not been handled.
+pub fn first_pending() -> Option<Signal> {
+    let pending = PENDING.load(Ordering::Relaxed);
+    Signal::iterator().find(|&sig| pending & (1 << sig as u32) != 0)
+}
+
+/// Marks the shell as interrupted, as if it had been sent SIGINT.
+pub fn interrupt() {
+    PENDING.fetch_or(1 << Signal::SIGINT as u32, Ordering::Relaxed);
+}
+
+/// Forgets a Ctrl-C that nothing was running to be interrupted by.
+pub fn clear_interrupt() {
+    PENDING.fetch_and(!(1 << Signal::SIGINT as u32), Ordering::Relaxed);
+}
+
+/// The signals that have arrived since this was last called.
+fn take_pending() -> Vec<Signal> {
+    let pending = PENDING.swap(0, Ordering::Relaxed);
+    Signal::iterator()
+        .filter(|&sig| pending & (1 << sig as u32) != 0)
+        .collect()
+}
+
+/// A signal given by number or by name, with or without the `SIG` prefix.
+pub fn parse(name: &str) -> Option<Signal> {
+    if let Ok(number) = name.parse::<i32>() {
+        return Signal::try_from(number).ok();
+    }
+
+    let name = name.to_uppercase();
+    if name.starts_with("SIG") {
+        name.parse().ok()
+    } else {
+        format!("SIG{}", name).parse().ok()
+    }
+}
+
+/// The name a signal is trapped by, e.g. `INT` for SIGINT.
+pub fn trap_name(sig: Signal) -> &'static str {
+    sig.as_str().trim_start_matches("SIG")
+}
+
+impl Vm {
+    /// Runs the traps for any signals that have arrived. SIGINT stops the
+    /// code being run unless it's trapped.
+    pub(super) fn handle_signals(&mut self) -> Result<(), VmError> {
+        for sig in take_pending() {
+            match self.traps.get(trap_name(sig)).cloned() {
+                // An empty trap ignores the signal.
+                Some(code) if code.is_empty() => {}
+                Some(code) => {
+                    self.run_source("trap", code);
+                }
+                None if sig == Signal::SIGINT => return Err(VmError::Interrupted),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the `EXIT` trap, if one is set, as the shell exits with
+    /// `status`. Returns the status to exit with, which the trap can change
+    /// by running `exit`.
+    pub fn run_exit_trap(&mut self, status: ivalue) -> ivalue {
+        let Some(code) = self.traps.remove("EXIT") else {
+            return status;
+        };
+
+        self.run_source("trap", code);
+        match self.take_halt() {
+            Some(e @ (VmError::Exit(_) | VmError::CommandFailed(..))) => e.exit_status(),
+            _ => status,
+        }
+    }
+}
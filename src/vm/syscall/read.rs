@@ -12,7 +12,7 @@ impl Vm {
                 let mut buffer = String::new();
                 match stdin().read_line(&mut buffer) {
                     Ok(_) => Ok(Value::String(buffer)),
-                    Err(_) => return Err(VmError::InvalidOperation),
+                    Err(e) => Err(VmError::ReadFailed(e.to_string())),
                 }
             }
             _ => return Ok(Value::String(from)),
@@ -28,7 +28,7 @@ impl Vm {
 
                 let first_digit_pos = match t.find(|c: char| c.is_digit(10)) {
                     Some(x) => x,
-                    None => return Err(VmError::InvalidValue),
+                    None => return Err(VmError::NotANumber(t.trim_end().to_owned())),
                 };
 
                 let number_str = &t[first_digit_pos..]
@@ -40,27 +40,29 @@ impl Vm {
                     if let Ok(x) = number_str.parse::<fvalue>() {
                         Ok(Value::Float(x))
                     } else {
-                        return Err(VmError::InvalidValue);
+                        Err(VmError::NotANumber(t.trim_end().to_owned()))
                     }
                 } else if let Ok(x) = number_str.parse::<ivalue>() {
                     Ok(Value::Int(x))
                 } else {
-                    return Err(VmError::InvalidValue);
+                    Err(VmError::NotANumber(t.trim_end().to_owned()))
                 }
             }
-            _ => return Err(VmError::InvalidOperation),
+            _ => Err(VmError::InvalidConversion(from.type_name(), "number")),
         }
     }
 
     pub fn syscall_read(&mut self) -> Result<(), VmError> {
-        let from = self.pop_stack();
-        let what = self.pop_stack().to_native_string();
+        let from = self.pop_stack()?;
+        let what = self.pop_stack()?.to_native_string();
 
-        println!("syscall: {} from {}", what, from);
+        if self.options.trace_exec {
+            eprintln!("syscall: {} from {}", what, from);
+        }
 
         let value = match what.as_str() {
             "number" => self.read_number(from)?,
-            _ => return Err(VmError::InvalidOperation),
+            _ => return Err(VmError::UnknownReadFormat(what)),
         };
 
         self.push_stack(value);
@@ -0,0 +1,35 @@
+use super::{value::Value, vm::Vm};
+
+/// How a value is shown in a trace, with strings quoted so that e.g. `1`
+/// and `"1"` can be told apart.
+fn traced(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        value => value.to_string(),
+    }
+}
+
+impl Vm {
+    /// Prints the op at `offset`, which is about to be run, then the stack and
+    /// the locals in each scope, outermost first. This is what `set -o
+    /// trace-exec` shows.
+    pub(super) fn trace_op(&self, offset: usize) {
+        let op = match self.chunk().disassemble_at(offset) {
+            Ok(op) => op,
+            Err(e) => format!("{:08} disassembly failed: {:?}", offset, e),
+        };
+        eprintln!("{}", op);
+
+        let stack: Vec<String> = self.stack.iter().map(traced).collect();
+        eprintln!("    stack: [{}]", stack.join(", "));
+
+        for (depth, scope) in self.scopes.iter().enumerate() {
+            let locals: Vec<String> = scope
+                .locals()
+                .into_iter()
+                .map(|(name, local)| format!("@{}={}", name, traced(&local.value)))
+                .collect();
+            eprintln!("    scope {}: {}", depth, locals.join(" "));
+        }
+    }
+}
@@ -1,11 +1,14 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    mem,
+};
 
 pub use i32 as ivalue;
-pub const IVALUE_SIZE: usize = size_of::<ivalue>();
 
 pub use f64 as fvalue;
 
-use super::vm::VmError;
+use super::{redirect::Redirect, vm::VmError};
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -15,8 +18,12 @@ pub enum Value {
     String(String),
     Bool(bool),
     Map(HashMap<Value, Value>),
-    Command(String, Vec<Value>),
-    Function(u8, *const u8),
+    List(Vec<Value>),
+    /// An inclusive range of integers, e.g. `1..10`.
+    Range(ivalue, ivalue),
+    Command(String, Vec<Value>, Vec<Redirect>),
+    Pipeline(Vec<Value>),
+    Function(u8, usize, usize),
 }
 
 #[macro_export]
@@ -34,35 +41,145 @@ impl std::fmt::Display for Value {
             Value::Float(x) => write!(f, "{:?}", x),
             Value::String(x) => write!(f, "{}", x),
             Value::Map(x) => {
-                for (k, v) in x {
-                    _ = write!(f, "{} = {}", k, v);
+                write!(f, "{{")?;
+                for (i, (k, v)) in sorted_entries(x).into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", k, v)?;
+                }
+                write!(f, "}}")
+            }
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Range(from, to) => write!(f, "{}..{}", from, to),
+            Value::Command(cmd, args, _) => write!(f, "{}/{}", cmd, args.len()),
+            Value::Pipeline(stages) => {
+                for (i, stage) in stages.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", stage)?;
                 }
                 Ok(())
             }
-            Value::Command(cmd, args) => write!(f, "{}/{}", cmd, args.len()),
             Value::Bool(x) => write!(f, "{}", x),
-            Value::Function(arity, ptr) => write!(f, "function/{}@{:?}", arity, ptr),
-            // Value::Array(x) => {
-            //     for k in x {
-            //         _ = write!(f, "{}", k);
-            //     }
-            //     Ok(())
-            // }
+            Value::Function(arity, chunk_id, offset) => {
+                write!(f, "function/{}@{}:{:08}", arity, chunk_id, offset)
+            }
+        }
+    }
+}
+
+/// Values are equal if they have the same type and contents, so unlike
+/// `Op::Equal` no conversions are made: `1` and `"1"` are different map keys.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::None, Value::None) => true,
+            (Value::Int(x), Value::Int(y)) => x == y,
+            (Value::Float(x), Value::Float(y)) => x.to_bits() == y.to_bits(),
+            (Value::String(x), Value::String(y)) => x == y,
+            (Value::Bool(x), Value::Bool(y)) => x == y,
+            (Value::Map(x), Value::Map(y)) => x == y,
+            (Value::List(x), Value::List(y)) => x == y,
+            (Value::Range(a, b), Value::Range(c, d)) => a == c && b == d,
+            (Value::Command(a, b, c), Value::Command(d, e, f)) => a == d && b == e && c == f,
+            (Value::Pipeline(x), Value::Pipeline(y)) => x == y,
+            (Value::Function(a, b, c), Value::Function(d, e, f)) => a == d && b == e && c == f,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Value::None => {}
+            Value::Int(x) => x.hash(state),
+            Value::Float(x) => x.to_bits().hash(state),
+            Value::String(x) => x.hash(state),
+            Value::Bool(x) => x.hash(state),
+            // Entries have no order, so only the size is hashed.
+            Value::Map(x) => x.len().hash(state),
+            Value::List(x) => x.hash(state),
+            Value::Range(from, to) => (from, to).hash(state),
+            Value::Command(cmd, args, redirects) => (cmd, args, redirects).hash(state),
+            Value::Pipeline(stages) => stages.hash(state),
+            Value::Function(arity, chunk_id, offset) => (arity, chunk_id, offset).hash(state),
         }
     }
 }
 
+/// The entries of a map, ordered by key so that output is stable.
+pub fn sorted_entries(map: &HashMap<Value, Value>) -> Vec<(&Value, &Value)> {
+    let mut entries: Vec<(&Value, &Value)> = map.iter().collect();
+    entries.sort_by_cached_key(|(k, _)| k.to_native_string());
+    entries
+}
+
 impl Value {
+    /// The value a word stands for: a number if it reads as one, e.g. `42` or
+    /// `1.5`, or else the word itself.
+    pub fn from_word(word: &str) -> Value {
+        if let Ok(x) = word.parse::<ivalue>() {
+            Value::Int(x)
+        } else if let Ok(x) = word.parse::<fvalue>() {
+            Value::Float(x)
+        } else {
+            Value::String(word.to_owned())
+        }
+    }
+
+    /// The name of the value's type, as shown in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::None => "none",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Map(_) => "map",
+            Value::List(_) => "list",
+            Value::Range(..) => "range",
+            Value::Command(..) => "command",
+            Value::Pipeline(_) => "pipeline",
+            Value::Function(..) => "function",
+        }
+    }
+
     pub fn to_native_string(&self) -> String {
         match self {
             Value::None => format!("(empty)"),
             Value::Int(x) => format!("{}", x),
             Value::Float(x) => format!("{}", x),
             Value::String(x) => x.to_owned(),
-            Value::Map(_) => "".to_owned(),
-            Value::Command(x, _) => x.to_owned(),
+            Value::Map(_) => self.to_string(),
+            Value::List(items) => items
+                .iter()
+                .map(|item| item.to_native_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+            Value::Range(from, to) => format!("{}..{}", from, to),
+            Value::Command(x, ..) => x.to_owned(),
+            Value::Pipeline(stages) => stages
+                .iter()
+                .map(|stage| stage.to_native_string())
+                .collect::<Vec<String>>()
+                .join(" | "),
             Value::Bool(x) => format!("{}", x),
-            Value::Function(arity, _) => format!("function/{}", arity),
+            Value::Function(arity, ..) => format!("function/{}", arity),
         }
     }
 
@@ -72,9 +189,11 @@ impl Value {
             | Value::Int(0)
             | Value::Float(0.0)
             | Value::Bool(false)
-            | Value::Command(_, _) => false,
-            Value::String(x) => x.len() != 0,
-            Value::Map(x) => x.len() != 0,
+            | Value::Command(..)
+            | Value::Pipeline(_) => false,
+            Value::String(x) => !x.is_empty(),
+            Value::Map(x) => !x.is_empty(),
+            Value::List(x) => !x.is_empty(),
             _ => true,
         }
     }
@@ -84,17 +203,15 @@ impl Value {
             Value::None => Ok(0.0),
             Value::String(v) => match (*v).parse::<fvalue>() {
                 Ok(x) => Ok(x),
-                Err(_) => return Err(VmError::InvalidValue),
+                Err(_) => Err(VmError::NotANumber(v.to_owned())),
             },
             Value::Int(x) => Ok((*x) as fvalue),
             Value::Float(x) => Ok(*x),
-            Value::Map(_) => return Err(VmError::InvalidOperation),
-            Value::Command(..) => return Err(VmError::InvalidOperation),
             Value::Bool(x) => Ok(match x {
                 true => 1,
                 false => 0,
             } as fvalue),
-            Value::Function(..) => return Err(VmError::InvalidOperation),
+            _ => Err(VmError::InvalidConversion(self.type_name(), "float")),
         }
     }
 
@@ -103,17 +220,15 @@ impl Value {
             Value::None => Ok(0),
             Value::String(v) => match (*v).parse::<ivalue>() {
                 Ok(x) => Ok(x),
-                Err(_) => return Err(VmError::InvalidValue),
+                Err(_) => Err(VmError::NotANumber(v.to_owned())),
             },
             Value::Int(x) => Ok(*x),
             Value::Float(x) => Ok(*x as ivalue),
-            Value::Map(_) => return Err(VmError::InvalidOperation),
-            Value::Command(..) => return Err(VmError::InvalidOperation),
             Value::Bool(x) => Ok(match x {
                 true => 1,
                 false => 0,
             }),
-            Value::Function(..) => return Err(VmError::InvalidOperation),
+            _ => Err(VmError::InvalidConversion(self.type_name(), "int")),
         }
     }
 }
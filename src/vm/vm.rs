@@ -1,34 +1,220 @@
-use std::ptr;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    mem,
+    path::PathBuf,
+    ptr,
+    rc::Rc,
+};
+
+use nix::sys::signal::Signal;
 
-use crate::vm::evaluate::EvaluateContext;
+use crate::{diagnostic::Diagnostic, vm::evaluate::EvaluateContext};
 
 use super::{
-    chunk::bytecode_chunk::ByteCodeChunk,
+    builtins::{complete::CompletionSpec, Builtin},
+    chunk::{bytecode_chunk::ByteCodeChunk, verify::VerifyError},
+    glob,
+    iterate::Iteration,
+    jobs::Job,
     local::{Scope, ScopeSearch},
-    op::Op,
+    op::{Op, OP_SIZE},
+    options::Options,
+    redirect::{Redirect, RedirectKind},
+    signals,
     value::{fvalue, ivalue, Value},
 };
 
 #[derive(Debug)]
 pub enum VmError {
-    InvalidOperation,
-    InvalidValue,
-    LocalAlreadyDefined,
-    UndefinedLocal,
-    PinnedLocal,
-    UnknownSysCall,
+    /// Bytecode that can't be run, e.g. a count that isn't an int.
+    InvalidOperation(&'static str),
+    /// A binary operation on values of these (left, right) types.
+    InvalidOperands(Op, &'static str, &'static str),
+    /// A unary operation on a value of this type.
+    InvalidOperand(Op, &'static str),
+    /// A conversion from one type to another.
+    InvalidConversion(&'static str, &'static str),
+    NotANumber(String),
+    DivideByZero,
+    /// An int operation whose result doesn't fit in an int.
+    Overflow(Op),
+    /// A comparison with NaN.
+    Unordered,
+    LocalAlreadyDefined(String),
+    UndefinedLocal(String),
+    PinnedLocal(String),
+    UnknownSysCall(String),
+    StackUnderflow,
+    ScopeUnderflow,
+    /// An attempt to read past the end of the chunk being run.
+    EndOfChunk,
+    InvalidBytecode(VerifyError),
+    /// A function taking this many arguments was given this many.
+    ArityMismatch(u8, usize),
+    /// An index into a list or string of this length.
+    IndexOutOfRange(ivalue, usize),
+    ReadFailed(String),
+    UnknownReadFormat(String),
+    /// `exit` was run, with this status.
+    Exit(ivalue),
+    /// A command failed while `set -e` was on.
+    CommandFailed(String, ivalue),
+    /// Ctrl-C was pressed, or the shell was otherwise sent SIGINT.
+    Interrupted,
+}
+
+impl VmError {
+    /// The status the shell exits with when stopped by this error.
+    pub fn exit_status(&self) -> ivalue {
+        match self {
+            VmError::Exit(status) | VmError::CommandFailed(_, status) => *status,
+            VmError::Interrupted => 128 + Signal::SIGINT as ivalue,
+            _ => 1,
+        }
+    }
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::InvalidOperation(what) => write!(f, "invalid bytecode: {}", what),
+            VmError::InvalidOperands(op, left, right) => match op {
+                Op::Add => write!(f, "cannot add {} to {}", right, left),
+                Op::Subtract => write!(f, "cannot subtract {} from {}", right, left),
+                Op::Multiply => write!(f, "cannot multiply {} by {}", left, right),
+                Op::Divide => write!(f, "cannot divide {} by {}", left, right),
+                Op::Range => write!(f, "cannot make a range from {} to {}", left, right),
+                Op::Pipe => write!(f, "cannot pipe {} into {}", left, right),
+                Op::Index => write!(f, "cannot index {} with {}", left, right),
+                Op::Contains => write!(f, "cannot look for {} in {}", left, right),
+                _ => write!(f, "cannot compare {} with {}", left, right),
+            },
+            VmError::InvalidOperand(op, operand) => match op {
+                Op::Negate => write!(f, "cannot negate {}", operand),
+                Op::Length => write!(f, "cannot take the length of {}", operand),
+                Op::Slice => write!(f, "cannot slice {}", operand),
+                Op::Redirect => write!(f, "cannot redirect {}", operand),
+                Op::Background => write!(f, "cannot run {} in the background", operand),
+                Op::Call => write!(f, "cannot call {}", operand),
+                _ => write!(f, "cannot use {} here", operand),
+            },
+            VmError::InvalidConversion(from, to) => write!(f, "cannot convert {} to {}", from, to),
+            VmError::NotANumber(text) => write!(f, "expected a number but found \"{}\"", text),
+            VmError::DivideByZero => write!(f, "cannot divide by zero"),
+            VmError::Overflow(op) => {
+                let operation = match op {
+                    Op::Negate => "negation",
+                    Op::Add => "addition",
+                    Op::Subtract => "subtraction",
+                    Op::Multiply => "multiplication",
+                    Op::Divide => "division",
+                    _ => "operation",
+                };
+                write!(f, "int overflow in {}", operation)
+            }
+            VmError::Unordered => write!(f, "cannot compare with NaN"),
+            VmError::LocalAlreadyDefined(name) => write!(f, "`@{}` is already defined", name),
+            VmError::UndefinedLocal(name) => write!(f, "`@{}` is not defined", name),
+            VmError::PinnedLocal(name) => write!(f, "cannot change `@{}`, it is pinned", name),
+            VmError::UnknownSysCall(name) => write!(f, "unknown system call `{}`", name),
+            VmError::ArityMismatch(arity, given) => write!(
+                f,
+                "function takes {} argument{} but {} {} given",
+                arity,
+                if *arity == 1 { "" } else { "s" },
+                given,
+                if *given == 1 { "was" } else { "were" }
+            ),
+            VmError::IndexOutOfRange(index, len) => {
+                write!(f, "index {} is out of range for length {}", index, len)
+            }
+            VmError::StackUnderflow => write!(f, "stack underflow"),
+            VmError::ScopeUnderflow => write!(f, "scope underflow"),
+            VmError::EndOfChunk => write!(f, "unexpected end of bytecode"),
+            VmError::InvalidBytecode(e) => write!(f, "invalid bytecode: {}", e),
+            VmError::ReadFailed(reason) => write!(f, "cannot read from console: {}", reason),
+            VmError::UnknownReadFormat(what) => write!(f, "cannot read {}", what),
+            VmError::Exit(status) => write!(f, "exit {}", status),
+            VmError::CommandFailed(cmd, status) => {
+                write!(f, "`{}` failed with exit status {}", cmd, status)
+            }
+            VmError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl Diagnostic for VmError {
+    fn hint(&self) -> Option<String> {
+        match self {
+            VmError::InvalidOperand(Op::Redirect, _) => {
+                Some("only commands can be redirected".to_owned())
+            }
+            VmError::InvalidOperand(Op::Background, _) => {
+                Some("only commands can be run in the background".to_owned())
+            }
+            VmError::InvalidOperand(Op::Call, _) => Some("only functions can be called".to_owned()),
+            VmError::InvalidOperands(Op::Pipe, ..) => {
+                Some("only commands can be piped together".to_owned())
+            }
+            VmError::InvalidOperands(Op::Index, "list" | "string", _) => {
+                Some("lists and strings are indexed by int".to_owned())
+            }
+            VmError::UndefinedLocal(name) => {
+                Some(format!("define it first with `let @{} = ...`", name))
+            }
+            VmError::PinnedLocal(_) => Some("pinned variables can't be changed".to_owned()),
+            VmError::Overflow(_) => Some(format!(
+                "ints range from {} to {}; use a float such as `1.0` for larger numbers",
+                ivalue::MIN,
+                ivalue::MAX
+            )),
+            VmError::IndexOutOfRange(..) => {
+                Some("negative indices count back from the end, so -1 is the last item".to_owned())
+            }
+            VmError::UnknownReadFormat(_) => Some("try `read number from ...`".to_owned()),
+            VmError::CommandFailed(..) => {
+                Some("`set -e` is on, so the first failing command stops the shell".to_owned())
+            }
+            _ => None,
+        }
+    }
 }
 
-struct CallFrame {
-    ip: *const u8,
+pub(super) struct CallFrame {
+    chunk_id: usize,
+    ip: usize,
 }
 
 pub struct Vm {
-    chunk: ByteCodeChunk,
-    ip: *const u8,
-    stack: Vec<Value>,
+    /// Every chunk that may still be referenced by a function value. Code is
+    /// addressed by (index into this list, offset into the chunk), so chunks
+    /// run on earlier REPL lines stay callable.
+    chunks: Vec<ByteCodeChunk>,
+    chunk_id: usize,
+    ip: usize,
+    /// The offset of the op being run, for reporting errors.
+    op_offset: usize,
+    pub(super) stack: Vec<Value>,
     pub(super) scopes: Vec<Scope>,
     pub(super) frames: Vec<CallFrame>,
+    /// The iterations of the `for` loops currently running, innermost last.
+    iterations: Vec<Iteration>,
+    pub(super) options: Options,
+    pub(super) builtins: HashMap<String, Rc<dyn Builtin>>,
+    /// Directories saved by `pushd`, most recent last.
+    pub(super) dir_stack: Vec<PathBuf>,
+    /// The code set by `trap` to run for each signal, by name (e.g. `INT`),
+    /// and for `EXIT`.
+    pub(super) traps: HashMap<String, String>,
+    /// Commands started with a trailing `&`, and any that have been stopped.
+    pub(super) jobs: Vec<Job>,
+    /// What Tab completes the arguments of each command with, set by
+    /// `complete`.
+    pub(super) completions: HashMap<String, CompletionSpec>,
+    /// An error raised outside of an op, e.g. by `exit`, which stops the code
+    /// being run once the current op is done.
+    halted: Option<VmError>,
     current_frame: Option<CallFrame>,
 }
 
@@ -41,34 +227,60 @@ impl Vm {
         self.stack.push(v);
     }
 
-    pub(super) fn pop_stack(&mut self) -> Value {
-        if self.stack.is_empty() {
-            panic!(
-                "stack pop underflow at {:08}",
-                (self.ip.wrapping_sub(self.chunk.content.as_ptr() as usize) as usize)
-            )
-        }
-        self.stack.pop().unwrap()
+    pub(super) fn pop_stack(&mut self) -> Result<Value, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow)
     }
 
-    fn peek_stack(&self, _: usize) -> Value {
-        if let Some(last) = self.stack.last() {
-            last.clone()
-        } else {
-            panic!(
-                "stack peek underflow at {:08}",
-                (self.ip.wrapping_sub(self.chunk.content.as_ptr() as usize) as usize)
-            )
-        }
+    /// Pops the left-hand operand of a binary operator and converts it,
+    /// naming both operand types if it can't be.
+    pub(super) fn pop_operand<T>(
+        &mut self,
+        op: Op,
+        right: &'static str,
+        convert: fn(&Value) -> Result<T, VmError>,
+    ) -> Result<T, VmError> {
+        let left = self.pop_stack()?;
+        convert(&left).map_err(|e| match e {
+            VmError::InvalidConversion(..) => VmError::InvalidOperands(op, left.type_name(), right),
+            e => e,
+        })
     }
 
-    fn read_as<T>(&mut self) -> T {
-        let val: T;
-        unsafe {
-            val = ptr::read_unaligned(self.ip as *const T);
-            self.ip = self.ip.add(size_of::<T>());
+    pub(super) fn chunk(&self) -> &ByteCodeChunk {
+        &self.chunks[self.chunk_id]
+    }
+
+    fn read_as<T>(&mut self) -> Result<T, VmError> {
+        let content = &self.chunks[self.chunk_id].content;
+        if self.ip + size_of::<T>() > content.len() {
+            return Err(VmError::EndOfChunk);
         }
-        val
+
+        let val: T = unsafe { ptr::read_unaligned(content.as_ptr().add(self.ip) as *const T) };
+        self.ip += size_of::<T>();
+        Ok(val)
+    }
+
+    /// Takes the error the VM was halted with, if nothing has stopped for it
+    /// yet.
+    pub(super) fn take_halt(&mut self) -> Option<VmError> {
+        self.halted.take()
+    }
+
+    /// Stops the code being run once the current op is done. Only the first
+    /// reason given is kept.
+    pub(super) fn halt(&mut self, error: VmError) {
+        self.halted.get_or_insert(error);
+    }
+
+    /// Discards everything left over from code that failed part way through,
+    /// so that the next [`Vm::run`] starts cleanly. Globals are kept.
+    fn reset(&mut self) {
+        self.reset_stack();
+        self.scopes.truncate(1);
+        self.frames.clear();
+        self.iterations.clear();
+        self.halted = None;
     }
 
     pub fn run(
@@ -76,41 +288,141 @@ impl Vm {
         chunk: ByteCodeChunk,
         context: EvaluateContext,
     ) -> Result<Value, VmError> {
-        self.chunk = chunk;
-        self.ip = self.chunk.content.as_ptr();
+        // Only chunks that define functions can be referenced once they've
+        // finished running, so there's no need to keep any others.
+        if self.chunks.last().is_some_and(|c| c.functions.is_empty()) {
+            self.chunks.pop();
+        }
 
+        chunk.verify().map_err(VmError::InvalidBytecode)?;
+
+        self.chunks.push(chunk);
+        self.chunk_id = self.chunks.len() - 1;
+        self.ip = 0;
+
+        let result = self.execute(context);
+        if result.is_err() {
+            self.reset();
+        }
+        result
+    }
+
+    /// Runs a chunk part way through running another, as `source` does, and
+    /// then carries on with the outer one. An error is returned with its
+    /// position in the inner chunk, and anything the inner chunk left behind
+    /// is discarded.
+    pub(super) fn run_nested(
+        &mut self,
+        chunk: ByteCodeChunk,
+    ) -> Result<Value, (VmError, Option<(i64, i64)>)> {
+        chunk
+            .verify()
+            .map_err(|e| (VmError::InvalidBytecode(e), None))?;
+
+        let (chunk_id, ip, op_offset) = (self.chunk_id, self.ip, self.op_offset);
+        let frames = mem::take(&mut self.frames);
+        let depths = (self.stack.len(), self.scopes.len(), self.iterations.len());
+
+        self.chunks.push(chunk);
+        self.chunk_id = self.chunks.len() - 1;
+        self.ip = 0;
+
+        let result = self
+            .execute(EvaluateContext::None)
+            .map_err(|e| (e, self.error_position()));
+
+        if result.is_err() {
+            self.stack.truncate(depths.0);
+            self.scopes.truncate(depths.1);
+            self.iterations.truncate(depths.2);
+        }
+
+        if self.chunks.last().is_some_and(|c| c.functions.is_empty()) {
+            self.chunks.pop();
+        }
+
+        self.frames = frames;
+        (self.chunk_id, self.ip, self.op_offset) = (chunk_id, ip, op_offset);
+        result
+    }
+
+    /// Runs a function to the end part way through running other code, as
+    /// when it's invoked like a command, and then carries on with that code.
+    /// The function's result is evaluated in `context`.
+    pub(super) fn run_function(
+        &mut self,
+        (chunk_id, offset): (usize, usize),
+        args: Vec<Value>,
+        context: EvaluateContext,
+    ) -> Result<Value, VmError> {
+        let (caller_chunk_id, ip, op_offset) = (self.chunk_id, self.ip, self.op_offset);
+        let frames = mem::take(&mut self.frames);
+        let depths = (self.stack.len(), self.scopes.len(), self.iterations.len());
+
+        // The function's prologue takes its parameters off the stack.
+        self.stack.extend(args);
+        self.chunk_id = chunk_id;
+        self.ip = offset;
+
+        let result = self.execute(context);
+        if result.is_err() {
+            self.stack.truncate(depths.0);
+            self.scopes.truncate(depths.1);
+            self.iterations.truncate(depths.2);
+        }
+
+        self.frames = frames;
+        (self.chunk_id, self.ip, self.op_offset) = (caller_chunk_id, ip, op_offset);
+        result
+    }
+
+    fn execute(&mut self, context: EvaluateContext) -> Result<Value, VmError> {
         loop {
-            let op: Op = { self.read_as::<Op>() };
+            if let Some(error) = self.halted.take() {
+                return Err(error);
+            }
+            if signals::any_pending() {
+                self.handle_signals()?;
+            }
+
+            self.op_offset = self.ip;
+            if self.options.trace_exec {
+                self.trace_op(self.op_offset);
+            }
+            let op: Op = { self.read_as::<Op>()? };
 
             match op {
                 Op::Return => {
                     if !self.return_from_subroutine() {
                         // If we didn't return from a subroutine, we're exiting the root
                         // routine, so return the value to our caller.
-                        let value = self.pop_stack();
+                        let value = self.pop_stack()?;
                         let value = self.evaluate(value, context);
-                        return Ok(value);
+                        return match self.halted.take() {
+                            Some(error) => Err(error),
+                            None => Ok(value),
+                        };
                     }
                 }
 
                 Op::IntConstant => {
-                    let v = self.read_as::<ivalue>();
+                    let v = self.read_as::<ivalue>()?;
                     self.push_stack(Value::Int(v));
                 }
 
                 Op::FloatConstant => {
-                    let v = self.read_as::<fvalue>();
+                    let v = self.read_as::<fvalue>()?;
                     self.push_stack(Value::Float(v));
                 }
 
                 Op::StringConstant => {
-                    let string_id = self.read_as::<usize>();
-                    let s = self.chunk.get_string(string_id).to_owned();
+                    let string_id = self.read_as::<usize>()?;
+                    let s = self.chunk().get_string(string_id).to_owned();
                     self.push_stack(Value::String(s))
                 }
 
                 Op::BoolConstant => {
-                    let v = self.read_as::<bool>();
+                    let v = self.read_as::<bool>()?;
                     self.push_stack(Value::Bool(v))
                 }
 
@@ -118,69 +430,90 @@ impl Vm {
                     self.push_stack(Value::None);
                 }
 
-                Op::Negate => match self.pop_stack() {
-                    Value::Int(x) => self.push_stack(Value::Int(-x)),
+                Op::Not => {
+                    let val = self.pop_stack()?;
+                    let val = self.evaluate(val, EvaluateContext::Condition);
+                    self.push_stack(Value::Bool(!val.to_native_bool()));
+                }
+
+                Op::Negate => match self.pop_stack()? {
+                    Value::Int(x) => {
+                        let x = x.checked_neg().ok_or(VmError::Overflow(op))?;
+                        self.push_stack(Value::Int(x));
+                    }
                     Value::Float(x) => self.push_stack(Value::Float(-x)),
-                    _ => return Err(VmError::InvalidOperation),
+                    x => return Err(VmError::InvalidOperand(op, x.type_name())),
                 },
 
-                Op::Add => match self.pop_stack() {
-                    Value::None => match self.pop_stack() {
+                Op::Add if self.has_list_operand() => self.concat()?,
+
+                Op::Add => match self.pop_stack()? {
+                    Value::None => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::Int(0)),
                         Value::Int(y) => self.push_stack(Value::Int(y)),
                         Value::Float(y) => self.push_stack(Value::Float(y)),
                         Value::String(v) => match (*v).parse::<ivalue>() {
                             Ok(y) => self.push_stack(Value::Int(y)),
-                            Err(_) => return Err(VmError::InvalidValue),
+                            Err(_) => return Err(VmError::NotANumber(v)),
                         },
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "none")),
                     },
-                    Value::Int(x) => match self.pop_stack() {
+                    Value::Int(x) => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::Int(x)),
-                        Value::Int(y) => self.push_stack(Value::Int(y + x)),
+                        Value::Int(y) => {
+                            let sum = y.checked_add(x).ok_or(VmError::Overflow(op))?;
+                            self.push_stack(Value::Int(sum));
+                        }
                         Value::Float(y) => self.push_stack(Value::Float(y + x as fvalue)),
                         Value::String(y) => {
                             if let Ok(y) = y.parse::<ivalue>() {
-                                self.push_stack(Value::Int(y + x));
+                                let sum = y.checked_add(x).ok_or(VmError::Overflow(op))?;
+                                self.push_stack(Value::Int(sum));
                             } else if let Ok(y) = y.parse::<fvalue>() {
                                 self.push_stack(Value::Float(y + x as fvalue));
                             } else {
                                 self.push_stack(Value::String(y + &x.to_string()));
                             }
                         }
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "int")),
                     },
                     Value::Float(x) => {
-                        let y = self.pop_stack().to_fvalue()?;
+                        let y = self.pop_operand(op, "float", Value::to_fvalue)?;
                         self.push_stack(Value::Float(y + x));
                     }
                     Value::String(x) => {
-                        let y = self.pop_stack().to_native_string();
+                        let y = self.pop_stack()?.to_native_string();
                         self.push_stack(Value::String(y + &x));
                     }
-                    _ => return Err(VmError::InvalidOperation),
+                    x => {
+                        let y = self.pop_stack()?;
+                        return Err(VmError::InvalidOperands(op, y.type_name(), x.type_name()));
+                    }
                 },
 
-                Op::Multiply => match self.pop_stack() {
-                    Value::None => match self.pop_stack() {
+                Op::Multiply => match self.pop_stack()? {
+                    Value::None => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::None),
                         Value::Int(_) => self.push_stack(Value::Int(0)),
                         Value::Float(_) => self.push_stack(Value::Float(0.0)),
                         Value::String(_) => self.push_stack(Value::None),
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "none")),
                     },
-                    Value::Int(x) => match self.pop_stack() {
+                    Value::Int(x) => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::Int(0)),
-                        Value::Int(y) => self.push_stack(Value::Int(x * y)),
+                        Value::Int(y) => {
+                            let product = x.checked_mul(y).ok_or(VmError::Overflow(op))?;
+                            self.push_stack(Value::Int(product));
+                        }
                         Value::Float(y) => self.push_stack(Value::Float((x as fvalue) * y)),
                         Value::String(y) => self.push_stack(Value::String(y.repeat(x as usize))),
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "int")),
                     },
                     Value::Float(x) => {
-                        let y = self.pop_stack().to_fvalue()?;
+                        let y = self.pop_operand(op, "float", Value::to_fvalue)?;
                         self.push_stack(Value::Float(y * x));
                     }
-                    Value::String(x) => match self.pop_stack() {
+                    Value::String(x) => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::Int(0)),
                         Value::Int(y) => {
                             self.push_stack(Value::String(x.repeat(y as usize)));
@@ -188,31 +521,35 @@ impl Vm {
                         Value::Float(y) => {
                             self.push_stack(Value::String(x.repeat(y as usize)));
                         }
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "string")),
                     },
-                    _ => return Err(VmError::InvalidOperation),
+                    x => {
+                        let y = self.pop_stack()?;
+                        return Err(VmError::InvalidOperands(op, y.type_name(), x.type_name()));
+                    }
                 },
 
-                Op::Subtract => match self.pop_stack() {
-                    Value::None => match self.pop_stack() {
+                Op::Subtract => match self.pop_stack()? {
+                    Value::None => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::None),
                         Value::Int(y) => self.push_stack(Value::Int(y - 0)),
                         Value::Float(y) => self.push_stack(Value::Float(y - 0.0)),
                         Value::String(v) => match (*v).parse::<ivalue>() {
                             Ok(y) => self.push_stack(Value::Int(y - 0)),
-                            Err(_) => return Err(VmError::InvalidValue),
+                            Err(_) => return Err(VmError::NotANumber(v)),
                         },
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "none")),
                     },
                     Value::Int(x) => {
-                        let y = self.pop_stack().to_ivalue()?;
-                        self.push_stack(Value::Int(y - x));
+                        let y = self.pop_operand(op, "int", Value::to_ivalue)?;
+                        let difference = y.checked_sub(x).ok_or(VmError::Overflow(op))?;
+                        self.push_stack(Value::Int(difference));
                     }
                     Value::Float(x) => {
-                        let y = self.pop_stack().to_fvalue()?;
+                        let y = self.pop_operand(op, "float", Value::to_fvalue)?;
                         self.push_stack(Value::Float(y - x));
                     }
-                    Value::String(x) => match self.pop_stack() {
+                    Value::String(x) => match self.pop_stack()? {
                         Value::None => self.push_stack(Value::String("".to_owned())),
                         Value::Int(y) => {
                             let val = if (y as usize) >= (*x).len() {
@@ -232,72 +569,175 @@ impl Vm {
                         Value::String(y) => {
                             self.push_stack(Value::String((*y).replace(&(*x), "")));
                         }
-                        _ => return Err(VmError::InvalidOperation),
+                        y => return Err(VmError::InvalidOperands(op, y.type_name(), "string")),
                     },
-                    _ => return Err(VmError::InvalidOperation),
+                    x => {
+                        let y = self.pop_stack()?;
+                        return Err(VmError::InvalidOperands(op, y.type_name(), x.type_name()));
+                    }
                 },
 
-                Op::Divide => match self.pop_stack() {
-                    Value::None => return Err(VmError::InvalidOperation),
+                Op::Divide => match self.pop_stack()? {
+                    Value::Int(0) => return Err(VmError::DivideByZero),
                     Value::Int(x) => {
-                        let y = self.pop_stack().to_ivalue()?;
-                        self.push_stack(Value::Int(y / x));
+                        let y = self.pop_operand(op, "int", Value::to_ivalue)?;
+                        let quotient = y.checked_div(x).ok_or(VmError::Overflow(op))?;
+                        self.push_stack(Value::Int(quotient));
                     }
                     Value::Float(x) => {
-                        let y = self.pop_stack().to_fvalue()?;
+                        let y = self.pop_operand(op, "float", Value::to_fvalue)?;
                         self.push_stack(Value::Float(y / x));
                     }
-                    _ => return Err(VmError::InvalidOperation),
+                    x => {
+                        let y = self.pop_stack()?;
+                        return Err(VmError::InvalidOperands(op, y.type_name(), x.type_name()));
+                    }
                 },
 
                 Op::Equal => {
-                    let result = match self.pop_stack() {
-                        Value::None => match self.pop_stack() {
-                            Value::None => true,
-                            _ => false,
-                        },
-                        Value::Int(x) => {
-                            let y = self.pop_stack().to_ivalue()?;
-                            y == x
-                        }
-                        Value::Float(x) => {
-                            let y = self.pop_stack().to_fvalue()?;
-                            y == x
-                        }
-                        Value::String(x) => {
-                            let y = self.pop_stack().to_native_string();
-                            y == x
-                        }
-                        Value::Bool(x) => {
-                            let y = self.pop_stack().to_native_bool();
-                            y == x
-                        }
-                        Value::Map(_) => false,
-                        Value::Command(..) => return Err(VmError::InvalidOperation),
-                        Value::Function(..) => return Err(VmError::InvalidOperation),
-                    };
+                    let result = self.equals()?;
+                    self.push_stack(Value::Bool(result));
+                }
+
+                Op::NotEqual => {
+                    let result = !self.equals()?;
+                    self.push_stack(Value::Bool(result));
+                }
+
+                Op::Less => {
+                    let result = self.compare()?.is_lt();
+                    self.push_stack(Value::Bool(result));
+                }
+
+                Op::LessEqual => {
+                    let result = self.compare()?.is_le();
+                    self.push_stack(Value::Bool(result));
+                }
+
+                Op::Greater => {
+                    let result = self.compare()?.is_gt();
+                    self.push_stack(Value::Bool(result));
+                }
 
+                Op::GreaterEqual => {
+                    let result = self.compare()?.is_ge();
                     self.push_stack(Value::Bool(result));
                 }
 
+                Op::Compare => {
+                    let result = self.compare()? as ivalue;
+                    self.push_stack(Value::Int(result));
+                }
+
+                Op::Range => {
+                    let to = self.pop_stack()?;
+                    let from = self.pop_stack()?;
+                    match (from.to_ivalue(), to.to_ivalue()) {
+                        (Ok(from), Ok(to)) => self.push_stack(Value::Range(from, to)),
+                        _ => {
+                            return Err(VmError::InvalidOperands(
+                                op,
+                                from.type_name(),
+                                to.type_name(),
+                            ))
+                        }
+                    }
+                }
+
                 Op::Command => {
-                    let cmd = self.read_string_const();
+                    let cmd = self.read_string_const()?;
 
-                    if let Value::Int(arg_count) = self.pop_stack() {
+                    if let Value::Int(arg_count) = self.pop_stack()? {
                         let mut args = Vec::new();
                         for _ in 0..arg_count {
-                            args.push(self.pop_stack());
+                            args.push(self.pop_stack()?);
                         }
                         args.reverse();
 
-                        self.push_stack(Value::Command(cmd, args));
+                        self.push_stack(Value::Command(cmd, args, Vec::new()));
                     } else {
-                        return Err(VmError::InvalidOperation);
+                        return Err(VmError::InvalidOperation("argument count is not an int"));
                     }
                 }
 
+                Op::Redirect => {
+                    let kind = self.read_as::<RedirectKind>()?;
+                    let target = self.pop_stack()?.to_native_string();
+
+                    match self.pop_stack()? {
+                        Value::Command(cmd, args, mut redirects) => {
+                            redirects.push(Redirect { kind, target });
+                            self.push_stack(Value::Command(cmd, args, redirects));
+                        }
+                        x => return Err(VmError::InvalidOperand(op, x.type_name())),
+                    }
+                }
+
+                Op::List => {
+                    if let Value::Int(count) = self.pop_stack()? {
+                        self.make_list(count)?;
+                    } else {
+                        return Err(VmError::InvalidOperation("item count is not an int"));
+                    }
+                }
+
+                Op::Map => {
+                    if let Value::Int(count) = self.pop_stack()? {
+                        self.make_map(count)?;
+                    } else {
+                        return Err(VmError::InvalidOperation("entry count is not an int"));
+                    }
+                }
+
+                Op::Contains => self.contains()?,
+                Op::Index => self.index()?,
+                Op::Slice => self.slice()?,
+                Op::Length => self.length()?,
+
+                Op::Glob => {
+                    let pattern = self.pop_stack()?.to_native_string();
+                    let paths = glob::expand(&pattern)
+                        .into_iter()
+                        .map(Value::String)
+                        .collect();
+                    self.push_stack(Value::List(paths));
+                }
+
+                Op::Background => {
+                    let job = self.pop_stack()?;
+                    self.start_job(job)?;
+                    self.push_stack(Value::None);
+                }
+
+                Op::Pipe => {
+                    let right = self.pop_stack()?;
+                    let left = self.pop_stack()?;
+
+                    let mut stages = match left {
+                        Value::Command(..) => vec![left],
+                        Value::Pipeline(stages) => stages,
+                        _ => {
+                            return Err(VmError::InvalidOperands(
+                                op,
+                                left.type_name(),
+                                right.type_name(),
+                            ))
+                        }
+                    };
+
+                    match right {
+                        Value::Command(..) => stages.push(right),
+                        Value::Pipeline(more) => stages.extend(more),
+                        _ => {
+                            return Err(VmError::InvalidOperands(op, "command", right.type_name()))
+                        }
+                    }
+
+                    self.push_stack(Value::Pipeline(stages));
+                }
+
                 Op::GetEnv => {
-                    let name = self.read_string_const();
+                    let name = self.read_string_const()?;
                     match std::env::var(name) {
                         Ok(value) => self.push_stack(Value::String(value)),
                         Err(_) => self.push_stack(Value::String("".to_owned())),
@@ -305,8 +745,8 @@ impl Vm {
                 }
 
                 Op::SetEnv => {
-                    let name = self.read_string_const();
-                    let value = self.pop_stack();
+                    let name = self.read_string_const()?;
+                    let value = self.pop_stack()?;
                     match std::env::var(&name) {
                         Ok(original_value) => {
                             std::env::set_var(name, value.to_native_string());
@@ -320,11 +760,11 @@ impl Vm {
                 }
 
                 Op::BeginScope => self.begin_scope(),
-                Op::EndScope => self.end_scope(),
+                Op::EndScope => self.end_scope()?,
 
                 Op::SetLocal | Op::PinLocal | Op::DefineLocal => {
-                    let name = self.read_string_const();
-                    let value = self.pop_stack();
+                    let name = self.read_string_const()?;
+                    let value = self.pop_stack()?;
                     let actual = self.evaluate(value, EvaluateContext::Assignment);
                     match op {
                         Op::SetLocal => self.set_local(&name, actual.clone())?,
@@ -336,74 +776,149 @@ impl Vm {
                 }
 
                 Op::GetLocal => {
-                    let name = self.read_string_const();
+                    let name = self.read_string_const()?;
                     let result = self.get_local(&name, ScopeSearch::AllScopes);
                     match result {
                         Some(value) => self.push_stack(value.value.clone()),
-                        None => return Err(VmError::UndefinedLocal),
+                        None => return Err(VmError::UndefinedLocal(name)),
                     }
                 }
 
                 Op::Pop => {
-                    let v = self.pop_stack();
+                    let v = self.pop_stack()?;
                     self.evaluate(v, EvaluateContext::None);
                 }
 
                 Op::BranchIfFalse => {
-                    let dist = self.read_as::<usize>();
-                    let val = self.evaluate(self.peek_stack(0), EvaluateContext::None);
-                    if !val.to_native_bool() {
-                        self.ip = self.ip.wrapping_add(dist);
+                    let dist = self.read_as::<usize>()?;
+
+                    // Replace the condition with its evaluated value, so that a
+                    // command isn't run again when it's later popped.
+                    let val = self.pop_stack()?;
+                    let val = self.evaluate(val, EvaluateContext::Condition);
+                    let truthy = val.to_native_bool();
+                    self.push_stack(val);
+
+                    if !truthy {
+                        self.ip += dist;
                     }
                 }
 
                 Op::Branch => {
-                    let dist = self.read_as::<usize>();
-                    self.ip = self.ip.wrapping_add(dist);
+                    let dist = self.read_as::<usize>()?;
+                    self.ip += dist;
                 }
 
                 Op::BranchBack => {
-                    let dist = self.read_as::<usize>();
-                    self.ip = self.ip.wrapping_sub(dist);
+                    let dist = self.read_as::<usize>()?;
+                    self.ip -= dist;
+                }
+
+                Op::Iterate => {
+                    if let Value::Int(count) = self.pop_stack()? {
+                        let mut values = Vec::new();
+                        for _ in 0..count {
+                            values.push(self.pop_stack()?);
+                        }
+                        values.reverse();
+
+                        let iteration = self.iteration(values);
+                        self.iterations.push(iteration);
+                    } else {
+                        return Err(VmError::InvalidOperation("item count is not an int"));
+                    }
+                }
+
+                Op::IterateNext => {
+                    let dist = self.read_as::<usize>()?;
+
+                    let item = self.iterations.last_mut().and_then(|i| i.next());
+                    match item {
+                        Some(item) => self.push_stack(item),
+                        None => self.ip += dist,
+                    }
+                }
+
+                Op::IterateEnd => {
+                    self.iterations.pop();
                 }
 
                 Op::SysCall => {
-                    let call = self.read_string_const();
+                    let call = self.read_string_const()?;
                     match call.as_str() {
                         "read" => self.syscall_read()?,
-                        _ => return Err(VmError::UnknownSysCall),
+                        _ => return Err(VmError::UnknownSysCall(call)),
                     }
                 }
 
                 Op::FunctionDefinition => {
-                    let id = self.read_as::<usize>();
-                    if let Some(func) = self.chunk.functions.get(id) {
-                        self.push_stack(func.to_owned());
+                    let id = self.read_as::<usize>()?;
+                    if let Some(func) = self.chunk().functions.get(id) {
+                        let func = Value::Function(func.arity, self.chunk_id, func.offset);
+                        self.push_stack(func);
                     } else {
                         println!("function with id {} was not found", id);
                     }
                 }
 
+                Op::Call => {
+                    let arg_count = self.read_as::<u8>()?;
+
+                    // The arguments stay on the stack for the function's
+                    // prologue to bind; only the function itself is removed.
+                    let index = self
+                        .stack
+                        .len()
+                        .checked_sub(arg_count as usize + 1)
+                        .ok_or(VmError::InvalidOperation("missing call arguments"))?;
+
+                    match self.stack.remove(index) {
+                        Value::Function(arity, chunk_id, offset) => {
+                            if arity != arg_count {
+                                return Err(VmError::ArityMismatch(arity, arg_count as usize));
+                            }
+                            self.branch_to_subroutine(chunk_id, offset);
+                        }
+                        x => return Err(VmError::InvalidOperand(op, x.type_name())),
+                    }
+                }
+
                 x => {
-                    let offset = self.ip.wrapping_sub(self.chunk.content.as_ptr() as usize);
-                    println!("unknown op: {:?} at {:08}", x, offset as usize);
+                    return Err(VmError::InvalidBytecode(VerifyError::UnknownOp(
+                        self.ip - OP_SIZE,
+                        x as u8,
+                    )))
                 }
             }
         }
     }
 
-    fn read_string_const(&mut self) -> String {
-        let string_id = self.read_as::<usize>();
-        self.chunk.get_string(string_id).to_owned()
+    /// The (line, column) of the op that caused an error returned by
+    /// [`Vm::run`], if it was in the code passed to that call.
+    pub fn error_position(&self) -> Option<(i64, i64)> {
+        if self.chunk_id + 1 != self.chunks.len() {
+            return None;
+        }
+        self.chunk().position_at(self.op_offset)
+    }
+
+    fn read_string_const(&mut self) -> Result<String, VmError> {
+        let string_id = self.read_as::<usize>()?;
+        Ok(self.chunk().get_string(string_id).to_owned())
     }
 
-    pub(super) fn branch_to_subroutine(&mut self, ptr: *const u8) {
-        self.frames.push(CallFrame { ip: self.ip });
-        self.ip = ptr;
+    pub(super) fn branch_to_subroutine(&mut self, chunk_id: usize, offset: usize) {
+        self.frames.push(CallFrame {
+            chunk_id: self.chunk_id,
+            ip: self.ip,
+        });
+        self.chunk_id = chunk_id;
+        self.ip = offset;
     }
 
     pub(super) fn return_from_subroutine(&mut self) -> bool {
         if let Some(frame) = self.frames.pop() {
+            self.chunk_id = frame.chunk_id;
             self.ip = frame.ip;
             true
         } else {
@@ -412,14 +927,27 @@ impl Vm {
     }
 
     pub fn new() -> Vm {
-        let chunk = ByteCodeChunk::new();
-        Vm {
-            ip: chunk.content.as_ptr(),
-            chunk,
+        let mut vm = Vm {
+            chunks: Vec::new(),
+            chunk_id: 0,
+            ip: 0,
+            op_offset: 0,
             stack: Vec::new(),
             scopes: vec![Scope::new()],
             frames: Vec::new(),
+            iterations: Vec::new(),
+            options: Options::default(),
+            builtins: HashMap::new(),
+            dir_stack: Vec::new(),
+            traps: HashMap::new(),
+            jobs: Vec::new(),
+            completions: HashMap::new(),
+            halted: None,
             current_frame: None,
-        }
+        };
+        vm.register_core_builtins();
+        // `@status` (or `$?`) is 0 until a command has run.
+        vm.define_global("status".to_owned(), Value::Int(0));
+        vm
     }
 }